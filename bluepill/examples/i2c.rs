@@ -24,12 +24,13 @@ fn main() -> ! {
     let who_am_i = WhoAmI(1);
     let register = DebugRegister(2);
 
-    bus.write(who_am_i, register, &[3, 4]);
+    // Errors are ignored: a missing device results in a timeout or NACK.
+    let _ = bus.write(who_am_i, register, &[3, 4]);
     loop {
         millis(1);
-        bus.write(who_am_i, register, &[3, 4]);
+        let _ = bus.write(who_am_i, register, &[3, 4]);
         millis(1);
         let mut data = [0u8, 0u8];
-        bus.read(who_am_i, register, &mut data);
+        let _ = bus.read(who_am_i, register, &mut data);
     }
 }
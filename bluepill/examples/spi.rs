@@ -23,10 +23,12 @@ fn main() -> ! {
         speed: 1_000_000,
         mode: spi::Mode::Mode0,
         byteorder: spi::ByteOrder::MsbFirst,
+        ..Default::default()
     }
     .make(SPI);
 
-    gpio::configure(CSN, gpio::Mode::OuputPushPull(gpio::Speed::Max10MHz));
+    // Chip-select is pulled during each transfer.
+    spi.set_cs(Some(CSN));
 
     let byte = 0b0101_0101;
     let reg = spi::DebugRegister(byte);
@@ -34,14 +36,10 @@ fn main() -> ! {
         led.on();
         // Arbitrary data.
         let mut data = [19, 20];
-        // Pull chip-select.
-        gpio::write(CSN, false);
         // Write data over spi.
         spi.write(reg, &data);
         // Read data over spi.
         spi.read(reg, &mut data);
-        // Set chip-select.
-        gpio::write(CSN, true);
         led.off();
         micros(100);
     }
@@ -0,0 +1,158 @@
+//! Analog to digital converter.
+//!
+//! Example usage:
+//!
+//! ```
+//! gpio::configure(gpio::PA0, gpio::Mode::AnalogInput);
+//! let mut adc = adc::Adc::new(adc::AdcSel::Adc1);
+//!
+//! // Raw 12-bit value, full scale at 3.3V.
+//! let raw = adc.read_pin(gpio::PA0);
+//! let mv = raw as u32 * 3_300 / adc::MAX as u32;
+//! ```
+//!
+//! Conversions are blocking, and take about 3.5us per read at the default sample time.
+
+use crate::{clock, delay, gpio};
+use stm32f1xx_hal::pac::{Peripherals as DevicePeripherals, ADC1, ADC2};
+
+type AdcPtr = stm32f1xx_hal::pac::adc1::RegisterBlock;
+
+/// Largest conversion result.
+pub const MAX: u16 = 0xFFF;
+
+/// Highest allowed ADC clock speed in Hertz.
+const MAX_SPEED: u32 = 14_000_000;
+
+/// Sample time register value: 28.5 cycles.
+const SAMPLE_TIME: u32 = 0b011;
+
+/// Sample time register value for the internal channels: 239.5 cycles, as the temperature
+/// sensor requires at least 17.1us.
+const SAMPLE_TIME_INTERNAL: u32 = 0b111;
+
+/// First internal channel: the temperature sensor, followed by the reference voltage.
+const TEMPERATURE_CHANNEL: u8 = 16;
+
+/// Available ADC peripherals.
+#[derive(Copy, Clone, Debug)]
+pub enum AdcSel {
+    Adc1,
+    Adc2,
+}
+
+/// Single conversion ADC.
+///
+/// Use [new][Adc::new()] to power on and calibrate.
+#[derive(Debug)]
+pub struct Adc {
+    sel: AdcSel,
+}
+
+impl AdcSel {
+    #[inline]
+    fn ptr(&self) -> *const AdcPtr {
+        match self {
+            Self::Adc1 => ADC1::ptr(),
+            // Same register layout as ADC1.
+            Self::Adc2 => ADC2::ptr() as *const AdcPtr,
+        }
+    }
+}
+
+impl Adc {
+    /// Enable, power on, and calibrate the ADC.
+    ///
+    /// Sets the shared ADC prescaler, for an ADC clock of at most 14MHz.
+    pub fn new(sel: AdcSel) -> Self {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            let pclk = clock::apb2_speed();
+            dp.RCC.cfgr.modify(|_, w| match pclk.div_ceil(MAX_SPEED) {
+                0..=2 => w.adcpre().div2(),
+                3..=4 => w.adcpre().div4(),
+                5..=6 => w.adcpre().div6(),
+                _ => w.adcpre().div8(),
+            });
+            match sel {
+                AdcSel::Adc1 => dp.RCC.apb2enr.modify(|_, w| w.adc1en().enabled()),
+                AdcSel::Adc2 => dp.RCC.apb2enr.modify(|_, w| w.adc2en().enabled()),
+            }
+
+            let adc = &*sel.ptr();
+            // Power on, and wait for the ADC to stabilize.
+            adc.cr2.modify(|_, w| w.adon().set_bit());
+            delay::micros(1);
+
+            // Calibrate.
+            adc.cr2.modify(|_, w| w.rstcal().set_bit());
+            while adc.cr2.read().rstcal().bit_is_set() {}
+            adc.cr2.modify(|_, w| w.cal().set_bit());
+            while adc.cr2.read().cal().bit_is_set() {}
+
+            // Regular conversions are started by software.
+            adc.cr2.modify(|_, w| {
+                w.extsel().swstart();
+                w.exttrig().set_bit()
+            });
+        }
+        Self { sel }
+    }
+
+    /// Single conversion of a channel (0-17), blocks until complete.
+    ///
+    /// Channels 16 and 17 are the internal temperature sensor and reference voltage, of ADC1.
+    /// Reading them turns these on. Panics if the channel is above 17.
+    pub fn read_channel(&mut self, ch: u8) -> u16 {
+        assert!(ch <= TEMPERATURE_CHANNEL + 1, "ADC channel out of range");
+        unsafe {
+            let adc = &*self.sel.ptr();
+            let sample_time = if ch >= TEMPERATURE_CHANNEL {
+                if adc.cr2.read().tsvrefe().bit_is_clear() {
+                    // Power on the temperature sensor and reference voltage, and wait for them
+                    // to start.
+                    adc.cr2.modify(|_, w| w.tsvrefe().set_bit());
+                    delay::micros(10);
+                }
+                SAMPLE_TIME_INTERNAL
+            } else {
+                SAMPLE_TIME
+            };
+            let ch = ch as u32;
+            if ch < 10 {
+                let shift = 3 * ch;
+                adc.smpr2
+                    .modify(|r, w| w.bits(r.bits() & !(0b111 << shift) | sample_time << shift));
+            } else {
+                let shift = 3 * (ch - 10);
+                adc.smpr1
+                    .modify(|r, w| w.bits(r.bits() & !(0b111 << shift) | sample_time << shift));
+            }
+            // Sequence of one conversion.
+            adc.sqr1.write(|w| w.l().bits(0));
+            adc.sqr3.write(|w| w.sq1().bits(ch as u8));
+            adc.cr2.modify(|_, w| w.swstart().set_bit());
+            while adc.sr.read().eoc().bit_is_clear() {}
+            // Reading the data register clears the end of conversion flag.
+            adc.dr.read().data().bits()
+        }
+    }
+
+    /// Single conversion of an analog input pin, blocks until complete.
+    ///
+    /// Panics if the pin has no ADC channel, see [channel].
+    #[inline]
+    pub fn read_pin(&mut self, pin: gpio::Gpio) -> u16 {
+        let ch = channel(pin).expect("Pin has no ADC channel");
+        self.read_channel(ch)
+    }
+}
+
+/// ADC channel of a pin: PA0..PA7 are channels 0-7, PB0 and PB1 are channels 8 and 9.
+pub fn channel(pin: gpio::Gpio) -> Option<u8> {
+    match pin.0 {
+        gpio::Port::A if (pin.1 as u8) < 8 => Some(pin.1 as u8),
+        gpio::Port::B if (pin.1 as u8) < 2 => Some(pin.1 as u8 + 8),
+        _ => None,
+    }
+}
@@ -0,0 +1,80 @@
+//! Bit-banding: atomic single bit access.
+//!
+//! Each bit in the first megabyte of the SRAM and peripheral regions is mapped to a word in an
+//! alias region. Writing the alias word sets or clears the bit, without a read-modify-write.
+//!
+//! Example usage:
+//!
+//! ```
+//! // GPIOC ODR, bit 13.
+//! let bit = bitband::Bit::new(0x4001_100C, 13);
+//! bit.set();
+//! ```
+
+use core::ptr;
+
+const SRAM_BASE: u32 = 0x2000_0000;
+const SRAM_ALIAS_BASE: u32 = 0x2200_0000;
+const PERIPHERAL_BASE: u32 = 0x4000_0000;
+const PERIPHERAL_ALIAS_BASE: u32 = 0x4200_0000;
+
+/// Size of the bit-band regions in bytes.
+const REGION_SIZE: u32 = 0x10_0000;
+
+/// Returns the alias address of a bit, or None if the address is not bit-bandable.
+#[inline]
+pub fn alias_address(address: u32, bit: u8) -> Option<u32> {
+    if bit >= 32 {
+        return None;
+    }
+    let (base, alias_base) = if (SRAM_BASE..SRAM_BASE + REGION_SIZE).contains(&address) {
+        (SRAM_BASE, SRAM_ALIAS_BASE)
+    } else if (PERIPHERAL_BASE..PERIPHERAL_BASE + REGION_SIZE).contains(&address) {
+        (PERIPHERAL_BASE, PERIPHERAL_ALIAS_BASE)
+    } else {
+        return None;
+    };
+    // Bits are numbered within the aligned word, 32 alias words per word.
+    let byte_offset = (address & !0b11) - base;
+    Some(alias_base + byte_offset * 32 + bit as u32 * 4)
+}
+
+/// Bit-band alias of a single bit.
+#[derive(Copy, Clone, Debug)]
+pub struct Bit(u32);
+
+impl Bit {
+    /// Alias of a bit in the word at `address`.
+    ///
+    /// Panics if the address is not in the SRAM or peripheral bit-band region, or if `bit >= 32`.
+    #[inline]
+    pub fn new(address: u32, bit: u8) -> Self {
+        Self(alias_address(address, bit).expect("address not bit-bandable"))
+    }
+
+    /// Alias address.
+    #[inline]
+    pub fn address(&self) -> u32 {
+        self.0
+    }
+
+    #[inline]
+    pub fn set(&self) {
+        self.write(true);
+    }
+
+    #[inline]
+    pub fn clear(&self) {
+        self.write(false);
+    }
+
+    #[inline]
+    pub fn write(&self, value: bool) {
+        unsafe { ptr::write_volatile(self.0 as *mut u32, value as u32) }
+    }
+
+    #[inline]
+    pub fn read(&self) -> bool {
+        unsafe { ptr::read_volatile(self.0 as *const u32) & 1 > 0 }
+    }
+}
@@ -0,0 +1,65 @@
+//! Backup data registers.
+//!
+//! Sixteen bit registers in the backup domain, which keep their value across resets. With a
+//! backup battery on `VBAT` they also survive power loss.
+//!
+//! Example usage:
+//!
+//! ```
+//! bkp::enable();
+//!
+//! let reboots = bkp::read(0);
+//! bkp::write(0, reboots.wrapping_add(1));
+//! ```
+//!
+//! # Note
+//!
+//! A [backup domain reset][crate::power::reset_backup_domain()] clears all registers, e.g. to
+//! change the [RTC clock source][crate::rtc::ClockSource].
+
+use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
+
+/// Number of backup data registers on low and medium-density devices.
+pub const COUNT: usize = 10;
+
+/// Number of backup data registers on high-density devices.
+pub const COUNT_HIGH_DENSITY: usize = 42;
+
+/// Enable the backup registers, and unlock the backup domain for writing.
+///
+/// See [power::enable_backup_domain][crate::power::enable_backup_domain()].
+#[inline]
+pub fn enable() {
+    crate::power::enable_backup_domain();
+}
+
+/// Read the backup data register.
+///
+/// Panics if the index is out of range.
+#[inline]
+pub fn read(index: usize) -> u16 {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        if index < COUNT {
+            dp.BKP.dr[index].read().bits() as u16
+        } else {
+            dp.BKP.bkp_dr[index - COUNT].read().bits() as u16
+        }
+    }
+}
+
+/// Write the backup data register.
+///
+/// Assumes the backup domain was [unlocked][enable()] before calling this.
+/// Panics if the index is out of range.
+#[inline]
+pub fn write(index: usize, value: u16) {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        if index < COUNT {
+            dp.BKP.dr[index].write(|w| w.bits(value as u32));
+        } else {
+            dp.BKP.bkp_dr[index - COUNT].write(|w| w.bits(value as u32));
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use super::Error;
+
+/// Largest 11-bit standard identifier.
+pub const MAX_STANDARD_ID: u16 = 0x7FF;
+
+/// Largest 29-bit extended identifier.
+pub const MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+
+/// CAN frame identifier.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Id {
+    /// 11-bit standard identifier.
+    Standard(u16),
+    /// 29-bit extended identifier.
+    Extended(u32),
+}
+
+/// CAN data or remote frame.
+///
+/// Holds at most eight data bytes.
+#[derive(Copy, Clone, Debug)]
+pub struct Frame {
+    id: Id,
+    remote: bool,
+    dlc: u8,
+    data: [u8; 8],
+}
+
+impl Frame {
+    /// Data frame with an 11-bit standard identifier.
+    ///
+    /// Returns [Err] if the id exceeds [MAX_STANDARD_ID], or data is longer than eight bytes.
+    #[inline]
+    pub fn new_standard(id: u16, data: &[u8]) -> Result<Self, Error> {
+        Self::new(Id::Standard(id), data)
+    }
+
+    /// Data frame with a 29-bit extended identifier.
+    ///
+    /// Returns [Err] if the id exceeds [MAX_EXTENDED_ID], or data is longer than eight bytes.
+    #[inline]
+    pub fn new_extended(id: u32, data: &[u8]) -> Result<Self, Error> {
+        Self::new(Id::Extended(id), data)
+    }
+
+    /// Data frame with the given identifier.
+    #[inline]
+    pub fn new(id: Id, data: &[u8]) -> Result<Self, Error> {
+        if data.len() > 8 {
+            return Err(Error::InvalidLength);
+        }
+        let mut frame = Self::new_remote(id, data.len() as u8)?;
+        frame.remote = false;
+        frame.data[..data.len()].copy_from_slice(data);
+        Ok(frame)
+    }
+
+    /// Remote frame, requesting `dlc` bytes.
+    #[inline]
+    pub fn new_remote(id: Id, dlc: u8) -> Result<Self, Error> {
+        let valid = match id {
+            Id::Standard(id) => id <= MAX_STANDARD_ID,
+            Id::Extended(id) => id <= MAX_EXTENDED_ID,
+        };
+        if !valid {
+            return Err(Error::InvalidId);
+        }
+        if dlc > 8 {
+            return Err(Error::InvalidLength);
+        }
+        Ok(Self {
+            id,
+            remote: true,
+            dlc,
+            data: [0u8; 8],
+        })
+    }
+
+    /// Frame identifier.
+    #[inline]
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    /// Returns whether the frame has an extended identifier.
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    /// Returns whether this is a remote frame.
+    #[inline]
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    /// Data length code.
+    #[inline]
+    pub fn dlc(&self) -> u8 {
+        self.dlc
+    }
+
+    /// Frame data.
+    ///
+    /// Empty for remote frames.
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.dlc as usize]
+        }
+    }
+
+    /// All eight data bytes, including unused bytes.
+    #[inline]
+    pub(crate) fn data_bytes(&self) -> &[u8; 8] {
+        &self.data
+    }
+
+    /// Encode identifier in the `TIxR` layout.
+    ///
+    /// - Bits[21:32]: Standard identifier, or the 11 msb of the extended identifier.
+    /// - Bits[3:21]:  The 18 lsb of the extended identifier.
+    /// - Bit[2]:      IDE, set for extended identifiers.
+    /// - Bit[1]:      RTR, set for remote frames.
+    ///
+    /// The TXRQ bit[0] is left cleared.
+    #[inline]
+    pub(crate) fn id_register(&self) -> u32 {
+        let rtr = (self.remote as u32) << 1;
+        match self.id {
+            Id::Standard(id) => (id as u32) << 21 | rtr,
+            Id::Extended(id) => id << 3 | 1 << 2 | rtr,
+        }
+    }
+
+    /// Decode frame from the `RIxR` and `RDTxR` registers, and the data bytes.
+    #[inline]
+    pub(crate) fn from_registers(rir: u32, rdtr: u32, data: [u8; 8]) -> Self {
+        let id = if rir & (1 << 2) > 0 {
+            Id::Extended(rir >> 3)
+        } else {
+            Id::Standard((rir >> 21) as u16)
+        };
+        Self {
+            id,
+            remote: rir & (1 << 1) > 0,
+            dlc: ((rdtr & 0xF) as u8).min(8),
+            data,
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! bxCAN peripheral.
+//!
+//! Example use:
+//!
+//! ```
+//! // Enable system clock.
+//! clock::init();
+//!
+//! // Create can bus.
+//! let mut bus = can::Config {
+//!     bitrate: 500_000,
+//!     loopback: false,
+//!     silent: false,
+//! }.make(can::Can::Can1(can::Port::B));
+//!
+//! // Transmit a frame with a standard identifier.
+//! let frame = can::Frame::new_standard(0x123, &[3, 4]).unwrap();
+//! bus.transmit(&frame);
+//!
+//! // Receive a frame.
+//! if let Some(frame) = bus.receive() {
+//!     let data = frame.data();
+//! }
+//! ```
+//!
+//! Note that the CAN and USB peripherals share the same packet buffer memory.
+
+mod frame;
+mod pac;
+
+pub use frame::{Frame, Id, MAX_EXTENDED_ID, MAX_STANDARD_ID};
+pub use pac::{Can, Mailbox, Port, TxStatus};
+
+/// Can peripheral configuration.
+///
+/// Use [make][Config::make()] to create a new [Bus].
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    /// Bitrate, must be nonzero.
+    pub bitrate: u32,
+    /// Loop transmitted frames back to the receiver.
+    pub loopback: bool,
+    /// Do not drive the TX pin.
+    pub silent: bool,
+}
+
+impl Config {
+    #[inline]
+    pub fn make(self, can: Can) -> Bus {
+        Bus::new(can, self)
+    }
+}
+
+/// Can bus errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Identifier does not fit in 11 (standard) or 29 (extended) bits.
+    InvalidId,
+    /// More than eight data bytes.
+    InvalidLength,
+    /// All transmit mailboxes are pending.
+    MailboxesFull,
+}
+
+/// Can bus.
+///
+/// Can be constructed using [Config][Config::make()].
+///
+/// All received frames are accepted.
+pub struct Bus {
+    can: Can,
+}
+
+impl Bus {
+    /// Panics if the bitrate is zero.
+    #[inline]
+    pub fn new(can: Can, config: Config) -> Self {
+        can.configure(config.bitrate, config.loopback, config.silent);
+        trace!("can: configured, bitrate = {}", config.bitrate);
+        Self { can }
+    }
+
+    /// Queue frame for transmission.
+    ///
+    /// Returns the mailbox holding the frame, or [Error::MailboxesFull].
+    #[inline]
+    pub fn transmit(&mut self, frame: &Frame) -> Result<Mailbox, Error> {
+        let mailbox = match self.can.empty_mailbox() {
+            Some(mailbox) => mailbox,
+            None => {
+                warn!("can: transmit mailboxes full");
+                return Err(Error::MailboxesFull);
+            }
+        };
+        self.can.write_mailbox(mailbox, frame);
+        trace!("can: queued frame, mailbox = {}", mailbox as u8);
+        Ok(mailbox)
+    }
+
+    /// Abort the pending transmission in mailbox.
+    ///
+    /// Use [transmit_status][Bus::transmit_status()] to check whether the frame was aborted, or
+    /// had already been transmitted.
+    #[inline]
+    pub fn abort(&mut self, mailbox: Mailbox) {
+        self.can.abort(mailbox);
+    }
+
+    /// Transmit status of mailbox.
+    #[inline]
+    pub fn transmit_status(&self, mailbox: Mailbox) -> TxStatus {
+        self.can.transmit_status(mailbox)
+    }
+
+    /// Receive frame.
+    ///
+    /// Returns None if both receive fifos are empty.
+    #[inline]
+    pub fn receive(&mut self) -> Option<Frame> {
+        (0..2)
+            .find(|&fifo| self.can.fifo_pending(fifo) > 0)
+            .map(|fifo| self.can.read_fifo(fifo))
+    }
+
+    /// Returns TX pin of current CAN peripheral.
+    #[inline]
+    pub fn get_tx_pin(&self) -> crate::gpio::Gpio {
+        self.can.get_tx_pin()
+    }
+
+    /// Returns RX pin of current CAN peripheral.
+    #[inline]
+    pub fn get_rx_pin(&self) -> crate::gpio::Gpio {
+        self.can.get_rx_pin()
+    }
+}
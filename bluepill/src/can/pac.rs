@@ -0,0 +1,247 @@
+use super::Frame;
+use crate::{clock, gpio};
+use stm32f1xx_hal::pac::{Peripherals as DevicePeripherals, CAN1};
+
+type CanPtr = stm32f1xx_hal::pac::can1::RegisterBlock;
+
+/// Available CAN peripherals.
+#[derive(Copy, Clone, Debug)]
+pub enum Can {
+    Can1(Port),
+}
+
+/// Available GPIO ports for Can1.
+///
+/// - Port A: RX = PA11, TX = PA12 (shared with USB).
+/// - Port B: RX = PB8, TX = PB9.
+#[derive(Copy, Clone, Debug)]
+pub enum Port {
+    A,
+    B,
+}
+
+/// Transmit mailbox.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mailbox {
+    M0 = 0,
+    M1 = 1,
+    M2 = 2,
+}
+
+/// Transmit mailbox status, as reported by `TSR`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TxStatus {
+    /// Mailbox holds a frame waiting for transmission.
+    pub pending: bool,
+    /// Last transmission or abort request has completed.
+    pub request_completed: bool,
+    /// Last transmission was successful.
+    pub transmission_ok: bool,
+    /// Last transmission failed due to arbitration lost.
+    pub arbitration_lost: bool,
+    /// Last transmission failed due to an error.
+    pub transmission_error: bool,
+}
+
+impl Can {
+    /// Get the pointer.
+    #[inline]
+    fn ptr(&self) -> *const CanPtr {
+        match self {
+            Self::Can1(_) => CAN1::ptr(),
+        }
+    }
+
+    pub fn get_tx_pin(&self) -> gpio::Gpio {
+        match self {
+            Self::Can1(Port::A) => gpio::PA12,
+            Self::Can1(Port::B) => gpio::PB9,
+        }
+    }
+
+    pub fn get_rx_pin(&self) -> gpio::Gpio {
+        match self {
+            Self::Can1(Port::A) => gpio::PA11,
+            Self::Can1(Port::B) => gpio::PB8,
+        }
+    }
+
+    pub fn configure_af_remap(&self) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            match self {
+                Self::Can1(Port::A) => dp.AFIO.mapr.modify(|_, w| w.can_remap().bits(0)),
+                Self::Can1(Port::B) => dp.AFIO.mapr.modify(|_, w| w.can_remap().bits(2)),
+            }
+        }
+    }
+
+    pub fn configure_gpio(&self) {
+        gpio::configure(self.get_rx_pin(), gpio::Mode::InputPullUp);
+        gpio::configure(
+            self.get_tx_pin(),
+            gpio::Mode::AlternateFunctionOutputPushPull(gpio::Speed::Max50MHz),
+        );
+    }
+
+    /// Configure the peripheral, and leave initialization mode.
+    ///
+    /// A single filter bank is configured to accept all frames into FIFO 0. Panics if the bitrate
+    /// is zero.
+    #[inline]
+    pub fn configure(&self, bitrate: u32, loopback: bool, silent: bool) {
+        assert!(bitrate > 0, "CAN bitrate must be nonzero");
+        unsafe {
+            // Enable the peripheral.
+            let dp = DevicePeripherals::steal();
+            match self {
+                Self::Can1(_) => dp.RCC.apb1enr.modify(|_, w| w.canen().enabled()),
+            }
+            gpio::enable_alternate_function_io();
+            self.configure_af_remap();
+            self.configure_gpio();
+
+            let can = &*self.ptr();
+
+            // Request initialization mode, and leave sleep mode.
+            can.mcr.modify(|_, w| {
+                w.sleep().clear_bit();
+                w.inrq().set_bit()
+            });
+            while can.msr.read().inak().bit_is_clear() {}
+
+            can.mcr.modify(|_, w| {
+                // Automatic bus-off management.
+                w.abom().set_bit();
+                // Automatic retransmission.
+                w.nart().clear_bit();
+                // Transmit priority by identifier.
+                w.txfp().clear_bit()
+            });
+
+            // Bit timing, clock obtained from APB1.
+            let (brp, ts1, ts2) = bit_timing(clock::apb1_speed(), bitrate);
+            let btr = (silent as u32) << 31
+                | (loopback as u32) << 30
+                | (ts2 - 1) << 20
+                | (ts1 - 1) << 16
+                | (brp - 1);
+            can.btr.write(|w| w.bits(btr));
+
+            // Filter bank 0: 32-bit mask mode, all zero mask, assigned to FIFO 0.
+            can.fmr.modify(|_, w| w.finit().set_bit());
+            can.fa1r.modify(|r, w| w.bits(r.bits() & !1));
+            can.fs1r.modify(|r, w| w.bits(r.bits() | 1));
+            can.fm1r.modify(|r, w| w.bits(r.bits() & !1));
+            can.ffa1r.modify(|r, w| w.bits(r.bits() & !1));
+            can.fb[0].fr1.write(|w| w.bits(0));
+            can.fb[0].fr2.write(|w| w.bits(0));
+            can.fa1r.modify(|r, w| w.bits(r.bits() | 1));
+            can.fmr.modify(|_, w| w.finit().clear_bit());
+
+            // Leave initialization mode.
+            can.mcr.modify(|_, w| w.inrq().clear_bit());
+            while can.msr.read().inak().bit_is_set() {}
+        }
+    }
+
+    /// Returns an empty transmit mailbox, if any.
+    #[inline]
+    pub fn empty_mailbox(&self) -> Option<Mailbox> {
+        let tsr = unsafe { (*self.ptr()).tsr.read().bits() };
+        [Mailbox::M0, Mailbox::M1, Mailbox::M2]
+            .iter()
+            .copied()
+            .find(|&mailbox| tsr & (1 << (26 + mailbox as u32)) > 0)
+    }
+
+    /// Write frame to mailbox, and request transmission.
+    ///
+    /// Assumes the mailbox is empty.
+    #[inline]
+    pub fn write_mailbox(&self, mailbox: Mailbox, frame: &Frame) {
+        let data = frame.data_bytes();
+        unsafe {
+            let tx = &(*self.ptr()).tx[mailbox as usize];
+            tx.tdtr.write(|w| w.bits(frame.dlc() as u32));
+            tx.tdlr.write(|w| w.bits(u32::from_le_bytes([data[0], data[1], data[2], data[3]])));
+            tx.tdhr.write(|w| w.bits(u32::from_le_bytes([data[4], data[5], data[6], data[7]])));
+            // Writing TXRQ hands the mailbox to the peripheral.
+            tx.tir.write(|w| w.bits(frame.id_register() | 1));
+        }
+    }
+
+    /// Request abort of the pending transmission in mailbox.
+    ///
+    /// A frame which is already being transmitted is not aborted.
+    #[inline]
+    pub fn abort(&self, mailbox: Mailbox) {
+        unsafe {
+            // Writing zero to the other bits has no effect.
+            (*self.ptr())
+                .tsr
+                .write(|w| w.bits(1 << (7 + 8 * mailbox as u32)));
+        }
+    }
+
+    /// Read the transmit status of mailbox.
+    #[inline]
+    pub fn transmit_status(&self, mailbox: Mailbox) -> TxStatus {
+        let tsr = unsafe { (*self.ptr()).tsr.read().bits() };
+        let bits = tsr >> (8 * mailbox as u32);
+        TxStatus {
+            pending: tsr & (1 << (26 + mailbox as u32)) == 0,
+            request_completed: bits & 1 > 0,
+            transmission_ok: bits & (1 << 1) > 0,
+            arbitration_lost: bits & (1 << 2) > 0,
+            transmission_error: bits & (1 << 3) > 0,
+        }
+    }
+
+    /// Returns the number of frames pending in the receive fifo.
+    #[inline]
+    pub fn fifo_pending(&self, fifo: usize) -> u8 {
+        unsafe { (*self.ptr()).rfr[fifo].read().fmp().bits() }
+    }
+
+    /// Read the frame at the output of the receive fifo, and release it.
+    ///
+    /// Assumes the fifo is not empty.
+    #[inline]
+    pub fn read_fifo(&self, fifo: usize) -> Frame {
+        unsafe {
+            let rx = &(*self.ptr()).rx[fifo];
+            let rir = rx.rir.read().bits();
+            let rdtr = rx.rdtr.read().bits();
+            let low = rx.rdlr.read().bits().to_le_bytes();
+            let high = rx.rdhr.read().bits().to_le_bytes();
+            // Release the fifo output mailbox.
+            (*self.ptr()).rfr[fifo].modify(|_, w| w.rfom().set_bit());
+            let mut data = [0u8; 8];
+            data[0..4].copy_from_slice(&low);
+            data[4..8].copy_from_slice(&high);
+            Frame::from_registers(rir, rdtr, data)
+        }
+    }
+}
+
+/// Bit timing: (prescaler, time segment 1, time segment 2) in time quanta.
+///
+/// Searches for the number of time quanta per bit with the smallest bitrate error,
+/// keeping the sample point near 87.5%. The bitrate must be nonzero.
+fn bit_timing(pclk: u32, bitrate: u32) -> (u32, u32, u32) {
+    let mut best = (1, 13, 2);
+    let mut best_error = u32::MAX;
+    for quanta in (8..=18).rev() {
+        let brp = (pclk / bitrate.saturating_mul(quanta)).clamp(1, 1024);
+        let actual = pclk / (brp * quanta);
+        let error = actual.abs_diff(bitrate);
+        if error < best_error {
+            let ts2 = (quanta + 4) / 8;
+            best = (brp, quanta - 1 - ts2, ts2);
+            best_error = error;
+        }
+    }
+    best
+}
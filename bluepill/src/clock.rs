@@ -1,45 +1,175 @@
 //! System clock setup.
 
+use core::sync::atomic::{AtomicU32, Ordering};
 use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
 
-/// System clock speed in Hertz.
+/// System clock speed in Hertz, as configured by [init].
+///
+/// Use [sysclk_hz] for the actual speed.
 pub const SPEED: u32 = 72_000_000;
 
+/// HSI oscillator speed in Hertz.
+pub const HSI_SPEED: u32 = 8_000_000;
+
+/// External crystal speed in Hertz, as passed to [init_with].
+static HSE_SPEED: AtomicU32 = AtomicU32::new(8_000_000);
+
+/// Oscillator driving the system clock PLL.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockSource {
+    /// External crystal (HSE).
+    Hse,
+    /// Internal 8MHz RC oscillator (HSI).
+    Hsi,
+}
+
+/// Number of status reads to wait for the external crystal to start.
+const HSE_STARTUP_TIMEOUT: u32 = 100_000;
+
 /// Setup of the system clock.
 ///
-/// Assumes a `16Mhz` external crystal is used.
-pub unsafe fn init() {
+/// Assumes a `8Mhz` external crystal is used, see [init_with]. Falls back to
+/// [init_hsi_usb] if the crystal does not start.
+///
+/// # Safety
+///
+/// Reconfigures the system clock, call once at startup before configuring peripherals.
+pub unsafe fn init() -> ClockSource {
+    match init_with(8_000_000) {
+        Ok(source) => source,
+        Err(()) => init_hsi_usb(),
+    }
+}
+
+/// Setup of the system clock, for an external crystal of `hse_hz` Hertz.
+///
+/// Achievable system clock per crystal:
+///
+/// | Crystal | PLL            | SYSCLK    |
+/// |---------|----------------|-----------|
+/// | 8MHz    | div 1, mul 9   | 72MHz     |
+/// | 12MHz   | div 1, mul 6   | 72MHz     |
+/// | 16MHz   | div 2, mul 9   | 72MHz     |
+/// | 25MHz   | div 2, mul 5   | 62.5MHz   |
+///
+/// Timing follows the actual speed, see [sysclk_hz]. USB requires a 72MHz system clock, so is
+/// not available with a 25MHz crystal, see [usb_clock_hz]. Returns an error, without touching
+/// the clock setup, for any other crystal, or if the crystal does not start.
+///
+/// # Safety
+///
+/// Reconfigures the system clock, call once at startup before configuring peripherals.
+pub unsafe fn init_with(hse_hz: u32) -> Result<ClockSource, ()> {
+    let (prediv2, mul) = pll_config(hse_hz).ok_or(())?;
+
     let dp = DevicePeripherals::steal();
 
-    dp.FLASH.acr.write(|w| {
-        // Enable the prefetch buffer.
-        w.prftbe().set_bit();
-        // Flash half cycle access: disabled.
-        w.hlfcya().clear_bit();
-        // Latency: two wait states if 48MHz < SYSCLK <= 72MHz.
-        w.latency().ws2()
+    HSE_SPEED.store(hse_hz, Ordering::Relaxed);
+
+    // Enable HSE (crystal) and clock security.
+    dp.RCC.cr.modify(|_, w| {
+        w.csson().set_bit();
+        w.hseon().set_bit()
     });
 
-    while !dp.FLASH.acr.read().latency().is_ws2() {}
+    // Wait for HSE to become ready, a missing crystal never does.
+    let mut timeout = HSE_STARTUP_TIMEOUT;
+    while dp.RCC.cr.read().hserdy().is_not_ready() {
+        if timeout == 0 {
+            dp.RCC.cr.modify(|_, w| {
+                w.csson().clear_bit();
+                w.hseon().clear_bit()
+            });
+            return Err(());
+        }
+        timeout -= 1;
+    }
+
+    flash_wait_states();
 
     dp.RCC.cfgr.write(|w| {
         // HSE oscillator clock selected as PLL input clock.
         w.pllsrc().hse_div_prediv();
-        // PLL multitplication factor: mul 9
-        w.pllmul().mul9();
+        // HSE divider for PLL entry.
+        w.pllxtpre().bit(prediv2);
+        // PLL multitplication factor: register value is the factor minus two.
+        w.pllmul().bits(mul - 2);
         // APB low-speed prescaler:  div 2
         w.ppre1().div2();
         // USB prescaler: PLL clock is divided by 1.5
         w.usbpre().div1_5()
     });
 
-    // Enable HSE (crystal), PLL and clock security.
-    dp.RCC.cr.write(|w| {
-        w.csson().set_bit();
-        w.hseon().set_bit();
-        w.pllon().set_bit()
+    enable_pll();
+    Ok(ClockSource::Hse)
+}
+
+/// Setup of the system clock from the internal oscillator, for boards without a crystal.
+///
+/// Runs at 64MHz (HSI div 2, mul 16), which does not allow using USB.
+///
+/// # Safety
+///
+/// Reconfigures the system clock, call once at startup before configuring peripherals.
+pub unsafe fn init_hsi() -> ClockSource {
+    init_hsi_pll(16, false)
+}
+
+/// Setup of the system clock from the internal oscillator, at a USB compatible 48MHz.
+///
+/// Runs at 48MHz (HSI div 2, mul 12). The HSI is not accurate enough for USB according to the
+/// datasheet, though it usually works at room temperature.
+///
+/// # Safety
+///
+/// Reconfigures the system clock, call once at startup before configuring peripherals.
+pub unsafe fn init_hsi_usb() -> ClockSource {
+    init_hsi_pll(12, true)
+}
+
+/// Run the PLL from HSI div 2, with the given multiplication factor.
+unsafe fn init_hsi_pll(mul: u8, usb_div1: bool) -> ClockSource {
+    let dp = DevicePeripherals::steal();
+
+    flash_wait_states();
+
+    dp.RCC.cfgr.write(|w| {
+        // HSI oscillator clock / 2 selected as PLL input clock.
+        w.pllsrc().hsi_div2();
+        // PLL multitplication factor: register value is the factor minus two.
+        w.pllmul().bits(mul - 2);
+        // APB low-speed prescaler:  div 2
+        w.ppre1().div2();
+        // USB prescaler: PLL clock is not divided, or divided by 1.5
+        w.usbpre().bit(usb_div1)
     });
 
+    enable_pll();
+    ClockSource::Hsi
+}
+
+/// Set the flash latency for a system clock up to 72MHz.
+unsafe fn flash_wait_states() {
+    let dp = DevicePeripherals::steal();
+
+    dp.FLASH.acr.write(|w| {
+        // Enable the prefetch buffer.
+        w.prftbe().set_bit();
+        // Flash half cycle access: disabled.
+        w.hlfcya().clear_bit();
+        // Latency: two wait states if 48MHz < SYSCLK <= 72MHz.
+        w.latency().ws2()
+    });
+
+    while !dp.FLASH.acr.read().latency().is_ws2() {}
+}
+
+/// Enable the PLL, and switch to it as the system clock.
+unsafe fn enable_pll() {
+    let dp = DevicePeripherals::steal();
+
+    dp.RCC.cr.modify(|_, w| w.pllon().set_bit());
+
     // Wait for PLL to become ready.
     while !dp.RCC.cr.read().pllrdy().is_ready() {}
 
@@ -50,14 +180,78 @@ pub unsafe fn init() {
     while !dp.RCC.cfgr.read().sws().is_pll() {}
 }
 
+/// PLL settings for a given crystal: (divide HSE by two, multiplication factor).
+fn pll_config(hse_hz: u32) -> Option<(bool, u8)> {
+    match hse_hz {
+        8_000_000 => Some((false, 9)),
+        12_000_000 => Some((false, 6)),
+        16_000_000 => Some((true, 9)),
+        25_000_000 => Some((true, 5)),
+        _ => None,
+    }
+}
+
+/// Actual system clock speed in Hertz.
+///
+/// Computed from the active clock source and PLL settings.
+pub fn sysclk_hz() -> u32 {
+    let cfgr = unsafe { DevicePeripherals::steal().RCC.cfgr.read() };
+    if cfgr.sws().is_hse() {
+        HSE_SPEED.load(Ordering::Relaxed)
+    } else if cfgr.sws().is_pll() {
+        let input = if cfgr.pllsrc().is_hsi_div2() {
+            HSI_SPEED / 2
+        } else if cfgr.pllxtpre().is_div2() {
+            HSE_SPEED.load(Ordering::Relaxed) / 2
+        } else {
+            HSE_SPEED.load(Ordering::Relaxed)
+        };
+        // Factor is the register value plus two, up to 16.
+        let mul = (cfgr.pllmul().bits() as u32 + 2).min(16);
+        input * mul
+    } else {
+        HSI_SPEED
+    }
+}
+
+/// USB clock speed in Hertz: the PLL output, divided by the USB prescaler.
+///
+/// USB requires exactly 48MHz.
+pub fn usb_clock_hz() -> u32 {
+    let cfgr = unsafe { DevicePeripherals::steal().RCC.cfgr.read() };
+    if !cfgr.sws().is_pll() {
+        // The USB clock is only derived from the PLL.
+        return 0;
+    }
+    if cfgr.usbpre().is_div1() {
+        sysclk_hz()
+    } else {
+        sysclk_hz() * 2 / 3
+    }
+}
+
+/// AHB clock speed in Hertz, which also clocks the core, SysTick and the DWT cycle counter.
+///
+/// Computed from the active clock setup, like [sysclk_hz].
+pub fn hclk_hz() -> u32 {
+    let reg = unsafe { DevicePeripherals::steal().RCC.cfgr.read().hpre().bits() };
+    if (reg & 8) > 0 {
+        // Division by 32 is skipped.
+        let shift = (reg & 7) + 1;
+        sysclk_hz() >> if shift < 5 { shift } else { shift + 1 }
+    } else {
+        sysclk_hz()
+    }
+}
+
 /// Clock speed for Peripherals connected to APB1.
 pub(crate) unsafe fn apb1_speed() -> u32 {
     let dp = DevicePeripherals::steal();
     let reg = dp.RCC.cfgr.read().ppre1().bits();
     if ( reg & 4 ) > 0 {
-        SPEED >> ( (reg & 3) + 1 )
+        hclk_hz() >> ( (reg & 3) + 1 )
     } else {
-        SPEED
+        hclk_hz()
     }
 }
 
@@ -66,8 +260,90 @@ pub(crate) unsafe fn apb2_speed() -> u32 {
     let dp = DevicePeripherals::steal();
     let reg = dp.RCC.cfgr.read().ppre2().bits();
     if ( reg & 4 ) > 0 {
-        SPEED >> ( (reg & 3) + 1 )
+        hclk_hz() >> ( (reg & 3) + 1 )
+    } else {
+        hclk_hz()
+    }
+}
+
+/// Clock speed for timers connected to APB1.
+///
+/// Twice the APB1 speed if the APB1 prescaler divides.
+pub(crate) unsafe fn apb1_timer_speed() -> u32 {
+    let dp = DevicePeripherals::steal();
+    if dp.RCC.cfgr.read().ppre1().bits() & 4 > 0 {
+        2 * apb1_speed()
     } else {
-        SPEED
+        apb1_speed()
+    }
+}
+
+/// Clock speed for timers connected to APB2.
+///
+/// Twice the APB2 speed if the APB2 prescaler divides.
+pub(crate) unsafe fn apb2_timer_speed() -> u32 {
+    let dp = DevicePeripherals::steal();
+    if dp.RCC.cfgr.read().ppre2().bits() & 4 > 0 {
+        2 * apb2_speed()
+    } else {
+        apb2_speed()
+    }
+}
+
+/// LSE crystal speed in Hertz.
+pub const LSE_SPEED: u32 = 32_768;
+
+/// Nominal LSI oscillator speed in Hertz.
+pub const LSI_SPEED: u32 = 40_000;
+
+/// Enable the internal low speed oscillator (LSI), and wait until it is ready.
+pub fn enable_lsi() {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.csr.modify(|_, w| w.lsion().set_bit());
+        while dp.RCC.csr.read().lsirdy().bit_is_clear() {}
+    }
+}
+
+/// Enable the external low speed oscillator (LSE), and wait until it is ready.
+///
+/// The LSE lives in the backup domain, which must be [unlocked][crate::power::enable_backup_domain()]
+/// for writing.
+/// Hangs if no `32.768kHz` crystal is present.
+pub fn enable_lse() {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.bdcr.modify(|_, w| w.lseon().set_bit());
+        while dp.RCC.bdcr.read().lserdy().bit_is_clear() {}
+    }
+}
+
+/// Clock routed to the MCO pin.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum McoSource {
+    SysClk,
+    Hse,
+    Hsi,
+    /// PLL clock divided by 2.
+    PllDiv2,
+}
+
+/// Output a clock on the MCO pin (PA8), for debugging the clock tree.
+///
+/// Does not change the system clock. Occupies PA8, which conflicts with TIM1_CH1.
+/// The pin can not toggle faster than 50MHz.
+pub fn enable_mco(source: McoSource) {
+    crate::gpio::configure(
+        crate::gpio::PA8,
+        crate::gpio::Mode::AlternateFunctionOutputPushPull(crate::gpio::Speed::Max50MHz),
+    );
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.cfgr.modify(|_, w| match source {
+            McoSource::SysClk => w.mco().sysclk(),
+            McoSource::Hse => w.mco().hse(),
+            McoSource::Hsi => w.mco().hsi(),
+            McoSource::PllDiv2 => w.mco().pll(),
+        });
     }
 }
@@ -0,0 +1,86 @@
+//! CRC calculation unit.
+//!
+//! Computes a CRC-32 over 32-bit words, using the fixed Ethernet polynomial `0x04C11DB7`.
+//!
+//! Note that the result differs from the common (zlib) CRC-32:
+//! - The initial value is `0xFFFF_FFFF`, but there is no final XOR.
+//! - Each word is processed msb first, without bit reflection of the input or the output.
+//!
+//! Example usage:
+//!
+//! ```
+//! let mut crc = crc::Crc::new();
+//! let checksum = crc.feed_bytes(&[1, 2, 3, 4]);
+//! ```
+
+use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
+
+/// CRC calculation unit.
+pub struct Crc {}
+
+impl Crc {
+    /// Enable the CRC unit, and reset the calculation.
+    pub fn new() -> Self {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            dp.RCC.ahbenr.modify(|_, w| w.crcen().enabled());
+        }
+        let mut crc = Self {};
+        crc.reset();
+        crc
+    }
+
+    /// Reset the calculation to the initial value `0xFFFF_FFFF`.
+    #[inline]
+    pub fn reset(&mut self) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            dp.CRC.cr.write(|w| w.reset().set_bit());
+        }
+    }
+
+    /// Feed words to the calculation, and return the CRC.
+    ///
+    /// The calculation continues from the previous value, call [reset][Crc::reset()] to start over.
+    #[inline]
+    pub fn feed(&mut self, data: &[u32]) -> u32 {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            for &word in data {
+                dp.CRC.dr.write(|w| w.dr().bits(word));
+            }
+            dp.CRC.dr.read().bits()
+        }
+    }
+
+    /// Feed bytes to the calculation, and return the CRC.
+    ///
+    /// Bytes are packed into big endian words. If the length is not a multiple of four, the
+    /// last word is padded with zeros.
+    #[inline]
+    pub fn feed_bytes(&mut self, data: &[u8]) -> u32 {
+        let mut crc = self.read();
+        for chunk in data.chunks(4) {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            crc = self.feed(&[u32::from_be_bytes(word)]);
+        }
+        crc
+    }
+
+    /// Current CRC value.
+    #[inline]
+    pub fn read(&self) -> u32 {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            dp.CRC.dr.read().bits()
+        }
+    }
+}
+
+impl Default for Crc {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,25 @@
+//! Single-core `critical-section` implementation, enabled by the `critical-section` feature.
+//!
+//! Masks all interrupts using PRIMASK, and restores the previous state on release, so nested
+//! critical sections are allowed.
+
+use cortex_m::{interrupt, register::primask};
+
+struct SingleCoreCriticalSection;
+
+critical_section::set_impl!(SingleCoreCriticalSection);
+
+unsafe impl critical_section::Impl for SingleCoreCriticalSection {
+    unsafe fn acquire() -> critical_section::RawRestoreState {
+        let was_active = primask::read().is_active();
+        interrupt::disable();
+        was_active
+    }
+
+    unsafe fn release(was_active: critical_section::RawRestoreState) {
+        // Only re-enable interrupts if they were enabled before the critical section.
+        if was_active {
+            interrupt::enable();
+        }
+    }
+}
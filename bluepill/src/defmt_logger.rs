@@ -0,0 +1,92 @@
+//! `defmt` global logger over a USART, enabled by the `defmt` feature.
+//!
+//! Example usage:
+//!
+//! ```
+//! let peripheral = uart::Usart::Usart2;
+//! let bus = uart::Config {
+//!     baudrate: 1_000_000,
+//!     tx_pin: OutputMode::PushPull(Speed::Max50MHz),
+//!     ..Default::default()
+//! }.make(peripheral);
+//! bluepill::defmt_logger::init(peripheral);
+//!
+//! defmt::info!("Hello {}", 42);
+//! ```
+//!
+//! Frames are written blocking, with interrupts disabled. Messages logged before [init] are
+//! dropped. The binary must be linked with `-C link-arg=-Tdefmt.x`.
+
+use crate::uart::Usart;
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_m::interrupt::{self, Mutex};
+use cortex_m::register::primask;
+
+/// USART to write the log frames to.
+static USART: Mutex<Cell<Option<Usart>>> = Mutex::new(Cell::new(None));
+
+static ENCODER: Mutex<RefCell<defmt::Encoder>> = Mutex::new(RefCell::new(defmt::Encoder::new()));
+
+/// Logger is acquired.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Interrupts were enabled when the logger was acquired.
+static RESTORE: AtomicBool = AtomicBool::new(false);
+
+/// Write log frames to the USART peripheral.
+///
+/// The peripheral and its transmitter must be configured, e.g. using a [uart::Bus][crate::uart::Bus].
+#[inline]
+pub fn init(usart: Usart) {
+    interrupt::free(|cs| USART.borrow(cs).set(Some(usart)));
+}
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let active = primask::read().is_active();
+        interrupt::disable();
+        if TAKEN.swap(true, Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly");
+        }
+        RESTORE.store(active, Ordering::Relaxed);
+        with_encoder(|encoder, usart| encoder.start_frame(|bytes| write_usart(usart, bytes)));
+    }
+
+    unsafe fn flush() {
+        if let Some(usart) = interrupt::free(|cs| USART.borrow(cs).get()) {
+            while !usart.tx_buffer_empty() {}
+        }
+    }
+
+    unsafe fn release() {
+        with_encoder(|encoder, usart| encoder.end_frame(|bytes| write_usart(usart, bytes)));
+        TAKEN.store(false, Ordering::Relaxed);
+        if RESTORE.load(Ordering::Relaxed) {
+            interrupt::enable();
+        }
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        with_encoder(|encoder, usart| encoder.write(bytes, |bytes| write_usart(usart, bytes)));
+    }
+}
+
+#[inline]
+fn with_encoder(f: impl FnOnce(&mut defmt::Encoder, Option<Usart>)) {
+    interrupt::free(|cs| f(&mut ENCODER.borrow(cs).borrow_mut(), USART.borrow(cs).get()));
+}
+
+/// Blocking write, bytes are dropped if no USART is set.
+#[inline]
+fn write_usart(usart: Option<Usart>, bytes: &[u8]) {
+    if let Some(usart) = usart {
+        for &byte in bytes {
+            while !usart.tx_buffer_empty() {}
+            usart.write_data_reg(byte);
+        }
+    }
+}
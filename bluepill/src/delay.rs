@@ -1,18 +1,123 @@
 //! Block program for certain period of time.
 
-const MILLIS_COUNT: u32 = 48_000; // SPEED / 1_000;
-const MICROS_COUNT: u32 = 48; // SPEED / 1_000_000;
+use crate::clock;
 
+/// Core cycles per iteration of the [delay] loop on the Cortex-M3.
+const LOOP_CYCLES: u32 = 3;
+
+/// Busy loop of about `LOOP_CYCLES * n` core cycles, see [millis] for a delay in real time.
 pub use cortex_m::asm::delay as delay;
 
-/// Blocks program for *atleast* one millisecond.
+/// Blocks program for *atleast* `count` milliseconds.
+///
+/// Follows the [active core clock][clock::hclk_hz()], and is accurate to a few percent without
+/// interrupts. Use [millis_dwt] for exact timing.
 #[inline]
 pub fn millis(count: u32) {
-    cortex_m::asm::delay(MILLIS_COUNT * count);
+    cortex_m::asm::delay(clock::hclk_hz().div_ceil(1_000 * LOOP_CYCLES) * count);
 }
 
-/// Blocks program for *atleast* one microsecond.
+/// Blocks program for *atleast* `count` microseconds.
+///
+/// Follows the [active core clock][clock::hclk_hz()], see [millis].
 #[inline]
 pub fn micros(count: u32) {
-    cortex_m::asm::delay(MICROS_COUNT * count);
+    cortex_m::asm::delay(clock::hclk_hz().div_ceil(1_000_000 * LOOP_CYCLES) * count);
+}
+
+/// Enable the DWT cycle counter, used by [millis_dwt] and [micros_dwt].
+///
+/// Same as [profile::init][crate::profile::init()].
+#[inline]
+pub fn init_dwt() {
+    crate::profile::init();
+}
+
+/// Blocks program for *atleast* `count` milliseconds, using the DWT cycle counter.
+///
+/// Requires [init_dwt] to be called first, otherwise hangs.
+#[inline]
+pub fn millis_dwt(count: u32) {
+    let cycles = clock::hclk_hz() / 1_000;
+    for _ in 0..count {
+        wait_cycles(cycles);
+    }
+}
+
+/// Blocks program for *atleast* `count` microseconds, using the DWT cycle counter.
+///
+/// Requires [init_dwt] to be called first, otherwise hangs.
+#[inline]
+pub fn micros_dwt(count: u32) {
+    millis_dwt(count / 1_000);
+    wait_cycles(clock::hclk_hz() / 1_000_000 * (count % 1_000));
+}
+
+/// Spin until the cycle counter advanced by `cycles`.
+#[inline]
+fn wait_cycles(cycles: u32) {
+    let start = crate::profile::start();
+    // Elapsed uses a wrapping subtraction, so counter wraparound is fine.
+    while start.elapsed() < cycles {}
+}
+
+/// Delay provider, for drivers which take an `embedded-hal` delay.
+///
+/// Forwards to [millis] and [micros].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Delay {}
+
+impl Delay {
+    #[inline]
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+mod hal {
+    use super::Delay;
+    use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+
+    impl DelayMs<u32> for Delay {
+        #[inline]
+        fn delay_ms(&mut self, ms: u32) {
+            super::millis(ms);
+        }
+    }
+
+    impl DelayMs<u16> for Delay {
+        #[inline]
+        fn delay_ms(&mut self, ms: u16) {
+            super::millis(ms as u32);
+        }
+    }
+
+    impl DelayMs<u8> for Delay {
+        #[inline]
+        fn delay_ms(&mut self, ms: u8) {
+            super::millis(ms as u32);
+        }
+    }
+
+    impl DelayUs<u32> for Delay {
+        #[inline]
+        fn delay_us(&mut self, us: u32) {
+            super::micros(us);
+        }
+    }
+
+    impl DelayUs<u16> for Delay {
+        #[inline]
+        fn delay_us(&mut self, us: u16) {
+            super::micros(us as u32);
+        }
+    }
+
+    impl DelayUs<u8> for Delay {
+        #[inline]
+        fn delay_us(&mut self, us: u8) {
+            super::micros(us as u32);
+        }
+    }
 }
@@ -0,0 +1,106 @@
+//! Device level control.
+
+use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
+
+/// Cause of the last reset.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResetCause {
+    /// Power-on or power-down reset.
+    PowerOn,
+    /// External reset on the `NRST` pin.
+    Pin,
+    /// Software reset.
+    Software,
+    /// Independent watchdog reset.
+    IndependentWatchdog,
+    /// Window watchdog reset.
+    WindowWatchdog,
+    /// Illegal entry into stop or standby mode.
+    LowPower,
+    /// No reset flag was set, e.g. the flags were cleared before.
+    Unknown,
+}
+
+/// Returns the cause of the last reset, and clears the reset flags.
+///
+/// A power-on reset also sets the pin reset flag, and every reset drives the `NRST` pin, so the
+/// most specific cause is reported.
+pub fn reset_cause() -> ResetCause {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        let csr = dp.RCC.csr.read();
+        let cause = if csr.lpwrrstf().bit_is_set() {
+            ResetCause::LowPower
+        } else if csr.iwdgrstf().bit_is_set() {
+            ResetCause::IndependentWatchdog
+        } else if csr.wwdgrstf().bit_is_set() {
+            ResetCause::WindowWatchdog
+        } else if csr.sftrstf().bit_is_set() {
+            ResetCause::Software
+        } else if csr.porrstf().bit_is_set() {
+            ResetCause::PowerOn
+        } else if csr.pinrstf().bit_is_set() {
+            ResetCause::Pin
+        } else {
+            ResetCause::Unknown
+        };
+        // Clear the reset flags.
+        dp.RCC.csr.modify(|_, w| w.rmvf().set_bit());
+        cause
+    }
+}
+
+/// Reset the microcontroller.
+///
+/// This is the proper way to reboot from software, e.g. after receiving a reset command. Pending
+/// memory writes complete before the reset is requested. The reset cause reads as
+/// [ResetCause::Software] afterwards.
+pub fn system_reset() -> ! {
+    cortex_m::asm::dsb();
+    cortex_m::peripheral::SCB::sys_reset()
+}
+
+/// Start address of the system memory, holding the built-in bootloader.
+pub const SYSTEM_MEMORY: u32 = 0x1FFF_F000;
+
+/// Jump to the built-in bootloader in system memory.
+///
+/// Disables all interrupts, switches back to the internal oscillator, and starts the bootloader
+/// as if booted with `BOOT0` high. The stack pointer and reset handler are read from the system
+/// memory vector table. Use [system_reset] to return to the application.
+///
+/// Note that on the STM32F103 the bootloader only supports USART1, on PA9 and PA10.
+pub fn jump_to_bootloader() -> ! {
+    cortex_m::interrupt::disable();
+    unsafe {
+        let cp = cortex_m::Peripherals::steal();
+        let dp = DevicePeripherals::steal();
+
+        // Stop the SysTick timer, and disable and clear all interrupts.
+        cp.SYST.csr.write(0);
+        for i in 0..cp.NVIC.icer.len() {
+            cp.NVIC.icer[i].write(0xFFFF_FFFF);
+            cp.NVIC.icpr[i].write(0xFFFF_FFFF);
+        }
+
+        // Switch to the HSI, which is the clock the bootloader expects after reset.
+        dp.RCC.cr.modify(|_, w| w.hsion().set_bit());
+        while dp.RCC.cr.read().hsirdy().bit_is_clear() {}
+        dp.RCC.cfgr.reset();
+        while !dp.RCC.cfgr.read().sws().is_hsi() {}
+        dp.RCC.cr.modify(|_, w| {
+            w.pllon().clear_bit();
+            w.csson().clear_bit();
+            w.hseon().clear_bit()
+        });
+        dp.RCC.cir.write(|w| w.bits(0));
+
+        // Remap the vector table to system memory.
+        cp.SCB.vtor.write(SYSTEM_MEMORY);
+        cortex_m::asm::dsb();
+        cortex_m::asm::isb();
+
+        cortex_m::interrupt::enable();
+        cortex_m::asm::bootload(SYSTEM_MEMORY as *const u32)
+    }
+}
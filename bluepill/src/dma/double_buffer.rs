@@ -0,0 +1,120 @@
+use super::{Channel, Direction, Size};
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Item that can be transferred by DMA.
+pub trait Word: Copy {
+    const SIZE: Size;
+}
+
+impl Word for u8 {
+    const SIZE: Size = Size::Bits8;
+}
+
+impl Word for u16 {
+    const SIZE: Size = Size::Bits16;
+}
+
+impl Word for u32 {
+    const SIZE: Size = Size::Bits32;
+}
+
+/// Half of a [DoubleBuffer].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Half {
+    First,
+    Second,
+}
+
+/// Ping-pong buffer for gapless streaming.
+///
+/// The DMA has no native double buffer mode, so a single buffer is transferred in circular
+/// mode, and treated as two halves. The half transfer flag signals that the first half is
+/// ready, the transfer complete flag signals the second half. While one half is processed, the
+/// DMA fills (or drains) the other.
+///
+/// Processing a half must finish before the DMA wraps around to it again.
+pub struct DoubleBuffer<T: Word + 'static> {
+    channel: Channel,
+    buffer: &'static mut [T],
+}
+
+impl<T: Word> DoubleBuffer<T> {
+    /// Configure and start a circular transfer between the peripheral and `buffer`.
+    ///
+    /// Panics unless the buffer length is even, nonzero, and at most [u16::MAX].
+    ///
+    /// # Safety
+    ///
+    /// `peripheral_addr` must be the data register of a peripheral served by `channel`, as
+    /// required by [Channel::configure()]. The buffer is owned by the transfer until
+    /// [stop][DoubleBuffer::stop()].
+    pub unsafe fn new(
+        channel: Channel,
+        peripheral_addr: u32,
+        buffer: &'static mut [T],
+        direction: Direction,
+    ) -> Self {
+        let len = buffer.len();
+        assert!(len > 0 && len % 2 == 0 && len <= u16::MAX as usize, "Invalid DMA buffer length");
+        channel.configure(
+            peripheral_addr,
+            buffer.as_mut_ptr() as u32,
+            len as u16,
+            direction,
+            true,
+        );
+        channel.set_size(T::SIZE);
+        compiler_fence(Ordering::SeqCst);
+        channel.enable();
+        Self { channel, buffer }
+    }
+
+    /// Enable or disable the half transfer and transfer complete interrupts.
+    #[inline]
+    pub fn interrupt_enable(&mut self, enable: bool) {
+        self.channel.half_interrupt_enable(enable);
+        self.channel.complete_interrupt_enable(enable);
+    }
+
+    /// Returns the half that is ready for processing, if any, and clears its flag.
+    ///
+    /// Can be polled from the main loop, or called from the channel interrupt.
+    #[inline]
+    pub fn ready(&mut self) -> Option<Half> {
+        if self.channel.is_half_complete() {
+            self.channel.clear_half_complete_flag();
+            Some(Half::First)
+        } else if self.channel.is_complete() {
+            self.channel.clear_complete_flag();
+            Some(Half::Second)
+        } else {
+            None
+        }
+    }
+
+    /// Access a half of the buffer.
+    #[inline]
+    pub fn half(&mut self, half: Half) -> &mut [T] {
+        compiler_fence(Ordering::SeqCst);
+        let mid = self.buffer.len() / 2;
+        match half {
+            Half::First => &mut self.buffer[..mid],
+            Half::Second => &mut self.buffer[mid..],
+        }
+    }
+
+    /// Used DMA channel.
+    #[inline]
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    /// Stop the transfer, and release the buffer.
+    #[inline]
+    pub fn stop(self) -> &'static mut [T] {
+        self.channel.disable();
+        self.channel.clear_interrupt_flags();
+        compiler_fence(Ordering::SeqCst);
+        self.buffer
+    }
+}
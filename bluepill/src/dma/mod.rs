@@ -0,0 +1,224 @@
+//! DMA controller.
+//!
+//! Example use:
+//!
+//! ```
+//! // Transmit a buffer over USART1 (TX is served by DMA1 channel 4).
+//! let data = [3, 4];
+//! let channel = dma::DMA1_CH4;
+//! // Safety: the buffer outlives the transfer, which is awaited below.
+//! unsafe {
+//!     channel.configure(
+//!         usart1_dr_address,
+//!         data.as_ptr() as u32,
+//!         data.len() as u16,
+//!         dma::Direction::MemoryToPeripheral,
+//!         false,
+//!     )
+//! };
+//! channel.enable();
+//!
+//! // Wait for completion.
+//! while !channel.is_complete() {}
+//! channel.clear_interrupt_flags();
+//! ```
+//!
+//! The buffer must remain valid until the transfer completes. Wrappers such as [uart_tx] take a
+//! `&'static` buffer instead, and are safe.
+//!
+//! Event driven use, with the transfer complete interrupt:
+//!
+//! ```
+//! dma::DMA1_CH4.enable_complete_interrupt();
+//! dma::DMA1_CH4.unmask_interrupts();
+//!
+//! #[interrupt]
+//! fn DMA1_CHANNEL4() {
+//!     dma::DMA1_CH4.handle_interrupt();
+//! }
+//!
+//! // In the main loop:
+//! if dma::DMA1_CH4.take_complete() {
+//!     // Start the next transfer.
+//! }
+//! ```
+
+mod double_buffer;
+mod pac;
+
+pub use double_buffer::{DoubleBuffer, Half, Word};
+pub use pac::{Channel, Direction, Dma, Number, Priority, Size};
+
+use crate::spi::Spi;
+use crate::uart::Usart;
+use stm32f1xx_hal::pac::ADC1;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+pub const DMA1_CH1: Channel = Channel(Dma::Dma1, Number::C1);
+pub const DMA1_CH2: Channel = Channel(Dma::Dma1, Number::C2);
+pub const DMA1_CH3: Channel = Channel(Dma::Dma1, Number::C3);
+pub const DMA1_CH4: Channel = Channel(Dma::Dma1, Number::C4);
+pub const DMA1_CH5: Channel = Channel(Dma::Dma1, Number::C5);
+pub const DMA1_CH6: Channel = Channel(Dma::Dma1, Number::C6);
+pub const DMA1_CH7: Channel = Channel(Dma::Dma1, Number::C7);
+
+pub const DMA2_CH1: Channel = Channel(Dma::Dma2, Number::C1);
+pub const DMA2_CH2: Channel = Channel(Dma::Dma2, Number::C2);
+pub const DMA2_CH3: Channel = Channel(Dma::Dma2, Number::C3);
+pub const DMA2_CH4: Channel = Channel(Dma::Dma2, Number::C4);
+pub const DMA2_CH5: Channel = Channel(Dma::Dma2, Number::C5);
+
+/// Channel used by [memcpy].
+pub const MEMCPY_CHANNEL: Channel = DMA1_CH6;
+
+/// Copy bytes from `src` to `dst` using DMA, and block until complete.
+///
+/// Uses [MEMCPY_CHANNEL] in memory-to-memory mode, which must not be in use by a peripheral.
+///
+/// Panics if the slices differ in length.
+pub fn memcpy(src: &[u8], dst: &mut [u8]) {
+    assert_eq!(src.len(), dst.len());
+    let channel = MEMCPY_CHANNEL;
+    // The transfer count register is 16 bits wide.
+    for (src, dst) in src
+        .chunks(u16::MAX as usize)
+        .zip(dst.chunks_mut(u16::MAX as usize))
+    {
+        // Safety: both slices outlive the transfer, which completes before the next chunk.
+        unsafe {
+            channel.configure(
+                dst.as_mut_ptr() as u32,
+                src.as_ptr() as u32,
+                src.len() as u16,
+                Direction::MemoryToPeripheral,
+                false,
+            )
+        };
+        channel.set_size(Size::Bits8);
+        channel.set_mem2mem();
+        compiler_fence(Ordering::SeqCst);
+        channel.enable();
+        while !channel.is_complete() {}
+        compiler_fence(Ordering::SeqCst);
+        channel.disable();
+        channel.clear_interrupt_flags();
+    }
+}
+
+/// DMA channel serving the transmit requests of the USART peripheral.
+#[inline]
+pub fn uart_tx_channel(usart: Usart) -> Channel {
+    match usart {
+        Usart::Usart1(_) => DMA1_CH4,
+        Usart::Usart2 => DMA1_CH7,
+        Usart::Usart3 => DMA1_CH2,
+    }
+}
+
+/// Start transmitting `data` over the USART peripheral using DMA.
+///
+/// Returns the used channel, which can be polled for [completion][Channel::is_complete()].
+/// Empty `data` is not transferred, and leaves the channel disabled.
+/// Assumes the USART peripheral and its transmitter are enabled.
+///
+/// Panics if `data` is longer than [u16::MAX] bytes, the largest transfer.
+pub fn uart_tx(usart: Usart, data: &'static [u8]) -> Channel {
+    assert!(data.len() <= u16::MAX as usize, "DMA transfer too long");
+    let channel = uart_tx_channel(usart);
+    if data.is_empty() {
+        // A transfer of zero items never completes.
+        channel.disable();
+        return channel;
+    }
+    // Safety: the data is static, and only read.
+    unsafe {
+        channel.configure(
+            usart.data_reg_address(),
+            data.as_ptr() as u32,
+            data.len() as u16,
+            Direction::MemoryToPeripheral,
+            false,
+        )
+    };
+    channel.set_size(Size::Bits8);
+    usart.clear_transmission_complete();
+    usart.tx_dma_enable(true);
+    compiler_fence(Ordering::SeqCst);
+    channel.enable();
+    channel
+}
+
+/// DMA channel serving the transmit requests of the SPI peripheral.
+#[inline]
+pub fn spi_tx_channel(spi: Spi) -> Channel {
+    match spi {
+        Spi::Spi1(_) => DMA1_CH3,
+        Spi::Spi2 => DMA1_CH5,
+    }
+}
+
+/// Start transmitting `data` over the SPI peripheral using DMA.
+///
+/// Returns the used channel, which can be polled for [completion][Channel::is_complete()].
+/// Received bytes are not read, leaving the overrun flag set. Empty `data` is not transferred,
+/// and leaves the channel disabled.
+/// Assumes the SPI peripheral is enabled, with 8-bit frames.
+///
+/// Panics if `data` is longer than [u16::MAX] bytes, the largest transfer.
+pub fn spi_tx(spi: Spi, data: &'static [u8]) -> Channel {
+    assert!(data.len() <= u16::MAX as usize, "DMA transfer too long");
+    let channel = spi_tx_channel(spi);
+    if data.is_empty() {
+        // A transfer of zero items never completes.
+        channel.disable();
+        return channel;
+    }
+    // Safety: the data is static, and only read.
+    unsafe {
+        channel.configure(
+            spi.data_reg_address(),
+            data.as_ptr() as u32,
+            data.len() as u16,
+            Direction::MemoryToPeripheral,
+            false,
+        )
+    };
+    channel.set_size(Size::Bits8);
+    spi.tx_dma_enable(true);
+    compiler_fence(Ordering::SeqCst);
+    channel.enable();
+    channel
+}
+
+/// DMA channel serving ADC1.
+pub const ADC1_CHANNEL: Channel = DMA1_CH1;
+
+/// Continuously transfer ADC1 conversions into `buf` using DMA.
+///
+/// Configures [ADC1_CHANNEL] in circular mode, with 16-bit items, and enables the ADC1 DMA
+/// request. The buffer is refilled from the start after each pass. ADC1 must be configured
+/// separately in scan and/or continuous mode, with one buffer entry per converted channel.
+///
+/// Returns the used channel. Panics if the buffer is empty, or longer than [u16::MAX] items.
+pub fn adc_transfer(buf: &'static mut [u16]) -> Channel {
+    assert!(!buf.is_empty() && buf.len() <= u16::MAX as usize, "Invalid DMA buffer length");
+    let channel = ADC1_CHANNEL;
+    let data_reg = unsafe { &(*ADC1::ptr()).dr as *const _ as u32 };
+    // Safety: the buffer is static, and borrowed by the transfer for good.
+    unsafe {
+        channel.configure(
+            data_reg,
+            buf.as_mut_ptr() as u32,
+            buf.len() as u16,
+            Direction::PeripheralToMemory,
+            true,
+        )
+    };
+    channel.set_size(Size::Bits16);
+    compiler_fence(Ordering::SeqCst);
+    channel.enable();
+    unsafe {
+        (*ADC1::ptr()).cr2.modify(|_, w| w.dma().set_bit());
+    }
+    channel
+}
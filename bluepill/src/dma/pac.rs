@@ -0,0 +1,386 @@
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use cortex_m::peripheral::NVIC;
+use stm32f1xx_hal::pac::Interrupt;
+use stm32f1xx_hal::pac::{dma1::CH, Peripherals as DevicePeripherals, DMA1, DMA2};
+
+type DmaPtr = stm32f1xx_hal::pac::dma1::RegisterBlock;
+
+/// Available DMA controllers.
+///
+/// DMA2 is only present on high-density devices.
+#[derive(Copy, Clone, Debug)]
+pub enum Dma {
+    Dma1,
+    Dma2,
+}
+
+/// Available DMA channels.
+///
+/// DMA1 has seven channels, DMA2 has five.
+#[repr(u8)]
+#[rustfmt::skip]
+#[derive(Copy, Clone, Debug)]
+pub enum Number {
+    C1 = 1,
+    C2 = 2,
+    C3 = 3,
+    C4 = 4,
+    C5 = 5,
+    C6 = 6,
+    C7 = 7,
+}
+
+/// Transfer direction.
+#[derive(Copy, Clone, Debug)]
+pub enum Direction {
+    PeripheralToMemory,
+    MemoryToPeripheral,
+}
+
+/// Size of a single transferred item.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug)]
+pub enum Size {
+    Bits8 = 0,
+    Bits16 = 1,
+    Bits32 = 2,
+}
+
+/// Configured transfer length of each channel: DMA1 followed by DMA2.
+///
+/// The `CNDTR` register only holds the remaining count, so the total is stored here.
+#[rustfmt::skip]
+static LENGTHS: [AtomicU16; 14] = [
+    AtomicU16::new(0), AtomicU16::new(0), AtomicU16::new(0), AtomicU16::new(0),
+    AtomicU16::new(0), AtomicU16::new(0), AtomicU16::new(0), AtomicU16::new(0),
+    AtomicU16::new(0), AtomicU16::new(0), AtomicU16::new(0), AtomicU16::new(0),
+    AtomicU16::new(0), AtomicU16::new(0),
+];
+
+/// Channel priority.
+///
+/// When channels have equal priority, the lowest channel number wins.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug)]
+pub enum Priority {
+    Low = 0,
+    Medium = 1,
+    High = 2,
+    VeryHigh = 3,
+}
+
+/// Transfer complete events of each channel, set by [Channel::handle_interrupt()].
+#[rustfmt::skip]
+static COMPLETE: [AtomicBool; 14] = [
+    AtomicBool::new(false), AtomicBool::new(false), AtomicBool::new(false),
+    AtomicBool::new(false), AtomicBool::new(false), AtomicBool::new(false),
+    AtomicBool::new(false), AtomicBool::new(false), AtomicBool::new(false),
+    AtomicBool::new(false), AtomicBool::new(false), AtomicBool::new(false),
+    AtomicBool::new(false), AtomicBool::new(false),
+];
+
+/// DMA channel tuple struct.
+///
+/// Use the constants such as [DMA1_CH1][super::DMA1_CH1], which only name existing channels.
+#[derive(Copy, Clone, Debug)]
+pub struct Channel(pub(super) Dma, pub(super) Number);
+
+impl Dma {
+    #[inline]
+    fn ptr(self) -> *const DmaPtr {
+        match self {
+            Self::Dma1 => DMA1::ptr(),
+            Self::Dma2 => DMA2::ptr(),
+        }
+    }
+
+    /// Enable the DMA controller clock.
+    #[inline]
+    pub fn enable_rcc(self) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            match self {
+                Self::Dma1 => dp.RCC.ahbenr.modify(|_, w| w.dma1en().enabled()),
+                Self::Dma2 => dp.RCC.ahbenr.modify(|_, w| w.dma2en().enabled()),
+            }
+        }
+    }
+}
+
+impl Channel {
+    /// Pointer to the channel register cluster.
+    #[inline]
+    fn ch(&self) -> *const CH {
+        unsafe {
+            let dma = &*self.0.ptr();
+            match self.1 {
+                Number::C1 => &dma.ch1,
+                Number::C2 => &dma.ch2,
+                Number::C3 => &dma.ch3,
+                Number::C4 => &dma.ch4,
+                Number::C5 => &dma.ch5,
+                Number::C6 => &dma.ch6,
+                Number::C7 => &dma.ch7,
+            }
+        }
+    }
+
+    /// Index of the channel in [LENGTHS] and [COMPLETE].
+    #[inline]
+    fn index(&self) -> usize {
+        let offset = match self.0 {
+            Dma::Dma1 => 0,
+            Dma::Dma2 => 7,
+        };
+        offset + self.1 as usize - 1
+    }
+
+    /// Shift of the channel flags in the `ISR` and `IFCR` registers.
+    #[inline]
+    fn flag_shift(&self) -> u32 {
+        4 * (self.1 as u32 - 1)
+    }
+
+    /// Configure a transfer.
+    ///
+    /// Disables the channel, and enables the controller clock. The memory address is incremented
+    /// after each item, the peripheral address is fixed. Priority and item sizes are untouched.
+    ///
+    /// In circular mode the transfer restarts from the first item after the last, and never
+    /// completes.
+    ///
+    /// # Safety
+    ///
+    /// The DMA accesses the addresses behind the back of the compiler once the channel is
+    /// [enabled][Channel::enable()]. Until the transfer completes or the channel is disabled:
+    /// - `memory_addr` must be valid for `len` items of the [item size][Channel::set_size()].
+    ///   The program must not access the memory while the DMA writes it, or write it while the
+    ///   DMA reads it.
+    /// - `peripheral_addr` must be a peripheral data register, or in
+    ///   [memory-to-memory][Channel::set_mem2mem()] mode be valid for `len` items as well.
+    #[inline]
+    pub unsafe fn configure(
+        &self,
+        peripheral_addr: u32,
+        memory_addr: u32,
+        len: u16,
+        direction: Direction,
+        circular: bool,
+    ) {
+        self.0.enable_rcc();
+        self.disable();
+        self.clear_interrupt_flags();
+        let ch = &*self.ch();
+        ch.par.write(|w| w.bits(peripheral_addr));
+        ch.mar.write(|w| w.bits(memory_addr));
+        ch.ndtr.write(|w| w.bits(len as u32));
+        LENGTHS[self.index()].store(len, Ordering::Relaxed);
+        ch.cr.modify(|_, w| {
+            match direction {
+                Direction::PeripheralToMemory => w.dir().clear_bit(),
+                Direction::MemoryToPeripheral => w.dir().set_bit(),
+            };
+            w.circ().bit(circular);
+            w.mem2mem().clear_bit();
+            w.pinc().clear_bit();
+            w.minc().set_bit()
+        });
+    }
+
+    /// Set the size of the peripheral and memory items.
+    #[inline]
+    pub fn set_size(&self, size: Size) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| {
+                w.psize().bits(size as u8);
+                w.msize().bits(size as u8)
+            });
+        }
+    }
+
+    /// Set the channel priority.
+    #[inline]
+    pub fn set_priority(&self, priority: Priority) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| w.pl().bits(priority as u8));
+        }
+    }
+
+    /// Switch a configured transfer to memory-to-memory mode.
+    ///
+    /// The peripheral address is used as second memory address, and is incremented as well.
+    /// The transfer starts as soon as the channel is enabled, and can not be circular.
+    #[inline]
+    pub fn set_mem2mem(&self) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| {
+                w.circ().clear_bit();
+                w.pinc().set_bit();
+                w.mem2mem().set_bit()
+            });
+        }
+    }
+
+    /// Start the transfer.
+    #[inline]
+    pub fn enable(&self) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| w.en().set_bit());
+        }
+    }
+
+    /// Stop the transfer.
+    #[inline]
+    pub fn disable(&self) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| w.en().clear_bit());
+        }
+    }
+
+    /// Returns whether the channel is enabled.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        unsafe { (*self.ch()).cr.read().en().bit_is_set() }
+    }
+
+    /// Number of items left to transfer.
+    #[inline]
+    pub fn remaining(&self) -> u16 {
+        unsafe { (*self.ch()).ndtr.read().bits() as u16 }
+    }
+
+    /// Index of the next item to be transferred.
+    ///
+    /// In circular mode this is the write position in the buffer, and wraps to zero after the
+    /// last item. Otherwise it equals the configured length once the transfer completes.
+    #[inline]
+    pub fn position(&self) -> usize {
+        let len = LENGTHS[self.index()].load(Ordering::Relaxed) as usize;
+        len.saturating_sub(self.remaining() as usize)
+    }
+
+    /// Returns whether the transfer complete flag is set.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.read_flag(1)
+    }
+
+    /// Returns whether the half transfer flag is set.
+    #[inline]
+    pub fn is_half_complete(&self) -> bool {
+        self.read_flag(2)
+    }
+
+    /// Returns whether the transfer error flag is set.
+    #[inline]
+    pub fn has_error(&self) -> bool {
+        self.read_flag(3)
+    }
+
+    /// Clear the transfer complete flag.
+    #[inline]
+    pub fn clear_complete_flag(&self) {
+        self.clear_flag(1);
+    }
+
+    /// Clear the half transfer flag.
+    #[inline]
+    pub fn clear_half_complete_flag(&self) {
+        self.clear_flag(2);
+    }
+
+    /// Clear all interrupt flags of this channel.
+    #[inline]
+    pub fn clear_interrupt_flags(&self) {
+        // Clearing the global flag clears all flags of the channel.
+        self.clear_flag(0);
+    }
+
+    /// Enable or disable the transfer complete interrupt.
+    #[inline]
+    pub fn complete_interrupt_enable(&self, enable: bool) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| w.tcie().bit(enable));
+        }
+    }
+
+    /// Enable or disable the half transfer interrupt.
+    #[inline]
+    pub fn half_interrupt_enable(&self, enable: bool) {
+        unsafe {
+            (*self.ch()).cr.modify(|_, w| w.htie().bit(enable));
+        }
+    }
+
+    /// Enable the transfer complete interrupt.
+    #[inline]
+    pub fn enable_complete_interrupt(&self) {
+        self.complete_interrupt_enable(true);
+    }
+
+    /// Interrupt line of this channel.
+    ///
+    /// DMA2 channels 4 and 5 share a single line.
+    #[inline]
+    pub fn interrupt(&self) -> Interrupt {
+        match (self.0, self.1) {
+            (Dma::Dma1, Number::C1) => Interrupt::DMA1_CHANNEL1,
+            (Dma::Dma1, Number::C2) => Interrupt::DMA1_CHANNEL2,
+            (Dma::Dma1, Number::C3) => Interrupt::DMA1_CHANNEL3,
+            (Dma::Dma1, Number::C4) => Interrupt::DMA1_CHANNEL4,
+            (Dma::Dma1, Number::C5) => Interrupt::DMA1_CHANNEL5,
+            (Dma::Dma1, Number::C6) => Interrupt::DMA1_CHANNEL6,
+            (Dma::Dma1, Number::C7) => Interrupt::DMA1_CHANNEL7,
+            (Dma::Dma2, Number::C1) => Interrupt::DMA2_CHANNEL1,
+            (Dma::Dma2, Number::C2) => Interrupt::DMA2_CHANNEL2,
+            (Dma::Dma2, Number::C3) => Interrupt::DMA2_CHANNEL3,
+            (Dma::Dma2, _) => Interrupt::DMA2_CHANNEL4_5,
+        }
+    }
+
+    #[inline]
+    pub fn unmask_interrupts(&self) {
+        unsafe { NVIC::unmask(self.interrupt()) }
+    }
+
+    #[inline]
+    pub fn mask_interrupts(&self) {
+        NVIC::mask(self.interrupt())
+    }
+
+    /// Dispatch helper, to be called from the channel interrupt.
+    ///
+    /// If the transfer complete flag is set: clears it, and records the event for
+    /// [take_complete][Channel::take_complete()]. Returns whether the transfer completed.
+    #[inline]
+    pub fn handle_interrupt(&self) -> bool {
+        if self.is_complete() {
+            self.clear_complete_flag();
+            COMPLETE[self.index()].store(true, Ordering::Release);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether a transfer complete event was recorded by the interrupt, and resets it.
+    #[inline]
+    pub fn take_complete(&self) -> bool {
+        COMPLETE[self.index()].swap(false, Ordering::Acquire)
+    }
+
+    #[inline]
+    fn read_flag(&self, bit: u32) -> bool {
+        let isr = unsafe { (*self.0.ptr()).isr.read().bits() };
+        isr & (1 << (self.flag_shift() + bit)) > 0
+    }
+
+    #[inline]
+    fn clear_flag(&self, bit: u32) {
+        unsafe {
+            (*self.0.ptr())
+                .ifcr
+                .write(|w| w.bits(1 << (self.flag_shift() + bit)));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Flash memory interface.
+//!
+//! Example usage:
+//!
+//! ```
+//! let ob = flash::read_option_bytes();
+//! if ob.rdp_level == flash::RdpLevel::Level0 {
+//!     // Flash can be read by a debugger.
+//! }
+//! ```
+//!
+//! Programming the flash:
+//!
+//! ```
+//! let mut flash = flash::Flash::unlock();
+//! flash.erase_page(0x0800_FC00)?;
+//! flash.write_halfword(0x0800_FC00, 0x1234)?;
+//! // Flash is locked again when dropped.
+//! ```
+//!
+//! Erasing sets all bits of a page, after which each halfword can be written once. Writing a
+//! halfword that is not `0xFFFF` fails with [Error::Programming], unless writing `0x0000`.
+//! The CPU stalls on flash reads during programming, so code keeps running from flash.
+//!
+//! See [Storage] for storing values across power cycles.
+
+mod storage;
+
+pub use storage::Storage;
+
+use core::ptr;
+use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
+
+/// Start address of the flash memory.
+pub const START: u32 = 0x0800_0000;
+
+/// Size of a flash page in bytes, the smallest erasable unit.
+pub const PAGE_SIZE: u32 = 1024;
+
+/// Address of the flash size register, in KB.
+const SIZE_REG: u32 = 0x1FFF_F7E0;
+
+/// Key sequence unlocking the flash programming and erase controller.
+const KEY1: u32 = 0x4567_0123;
+const KEY2: u32 = 0xCDEF_89AB;
+
+/// Flash programming error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Address was not erased before writing.
+    Programming,
+    /// Address is write protected.
+    WriteProtected,
+    /// [Storage] has no room for another key.
+    Full,
+    /// Address is not inside the flash, or not aligned.
+    Address,
+}
+
+/// Flash read protection level.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RdpLevel {
+    /// No read protection.
+    Level0,
+    /// Read protection enabled.
+    Level1,
+}
+
+/// Option bytes, as loaded after the last reset.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OptionBytes {
+    /// Read protection level.
+    pub rdp_level: RdpLevel,
+    /// Watchdog is started by software. If false, the hardware independent watchdog is selected.
+    pub iwdg_sw: bool,
+    /// No reset is generated when entering stop mode.
+    pub nrst_stop: bool,
+    /// No reset is generated when entering standby mode.
+    pub nrst_standby: bool,
+    /// User data bytes.
+    pub data: [u8; 2],
+    /// Option bytes did not match their complement, and were loaded with default values.
+    pub error: bool,
+}
+
+/// Read the option bytes from the `OBR` register.
+pub fn read_option_bytes() -> OptionBytes {
+    let obr = unsafe { DevicePeripherals::steal().FLASH.obr.read().bits() };
+    OptionBytes {
+        rdp_level: if obr & (1 << 1) > 0 {
+            RdpLevel::Level1
+        } else {
+            RdpLevel::Level0
+        },
+        iwdg_sw: obr & (1 << 2) > 0,
+        nrst_stop: obr & (1 << 3) > 0,
+        nrst_standby: obr & (1 << 4) > 0,
+        data: [(obr >> 10) as u8, (obr >> 18) as u8],
+        error: obr & 1 > 0,
+    }
+}
+
+/// Unlocked flash programming and erase controller.
+///
+/// Locks the flash again when dropped.
+pub struct Flash {}
+
+impl Flash {
+    /// Unlock the flash for programming and erasing.
+    ///
+    /// Requires the HSI oscillator to be on, which [clock::init][crate::clock::init] leaves on.
+    pub fn unlock() -> Self {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            if dp.FLASH.cr.read().lock().bit_is_set() {
+                dp.FLASH.keyr.write(|w| w.key().bits(KEY1));
+                dp.FLASH.keyr.write(|w| w.key().bits(KEY2));
+            }
+        }
+        Self {}
+    }
+
+    /// Erase the page containing `addr`, setting all bits.
+    pub fn erase_page(&mut self, addr: u32) -> Result<(), Error> {
+        check_address(addr)?;
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            wait_ready();
+            dp.FLASH.cr.modify(|_, w| w.per().set_bit());
+            dp.FLASH.ar.write(|w| w.far().bits(addr));
+            dp.FLASH.cr.modify(|_, w| w.strt().set_bit());
+            wait_ready();
+            dp.FLASH.cr.modify(|_, w| w.per().clear_bit());
+        }
+        take_error()
+    }
+
+    /// Write a halfword to an even address.
+    ///
+    /// The address must be erased (`0xFFFF`), unless writing `0x0000`.
+    pub fn write_halfword(&mut self, addr: u32, value: u16) -> Result<(), Error> {
+        check_address(addr)?;
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            wait_ready();
+            dp.FLASH.cr.modify(|_, w| w.pg().set_bit());
+            ptr::write_volatile(addr as *mut u16, value);
+            wait_ready();
+            dp.FLASH.cr.modify(|_, w| w.pg().clear_bit());
+        }
+        take_error()
+    }
+}
+
+impl Drop for Flash {
+    fn drop(&mut self) {
+        unsafe {
+            DevicePeripherals::steal()
+                .FLASH
+                .cr
+                .modify(|_, w| w.lock().set_bit());
+        }
+    }
+}
+
+/// Size of the flash memory in bytes, as read from the device.
+#[inline]
+pub fn size() -> u32 {
+    unsafe { ptr::read_volatile(SIZE_REG as *const u16) as u32 * 1024 }
+}
+
+/// Read a halfword from an even flash address.
+#[inline]
+pub fn read_halfword(addr: u32) -> Result<u16, Error> {
+    check_address(addr)?;
+    Ok(unsafe { ptr::read_volatile(addr as *const u16) })
+}
+
+/// Returns an error unless `addr` is an even address inside the flash.
+#[inline]
+fn check_address(addr: u32) -> Result<(), Error> {
+    if addr % 2 == 0 && addr >= START && addr - START < size() {
+        Ok(())
+    } else {
+        Err(Error::Address)
+    }
+}
+
+/// Wait until no flash operation is ongoing.
+#[inline]
+unsafe fn wait_ready() {
+    let dp = DevicePeripherals::steal();
+    while dp.FLASH.sr.read().bsy().bit_is_set() {}
+}
+
+/// Read and clear the status flags of the last operation.
+fn take_error() -> Result<(), Error> {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        let sr = dp.FLASH.sr.read();
+        // Flags are cleared by writing one.
+        dp.FLASH.sr.write(|w| {
+            w.eop().set_bit();
+            w.pgerr().set_bit();
+            w.wrprterr().set_bit()
+        });
+        if sr.wrprterr().bit_is_set() {
+            Err(Error::WriteProtected)
+        } else if sr.pgerr().bit_is_set() {
+            Err(Error::Programming)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use super::{size, Error, Flash, PAGE_SIZE, START};
+use core::ptr;
+
+/// Page header: erased, never used.
+const ERASED: u16 = 0xFFFF;
+/// Page header: page holds the current values.
+const ACTIVE: u16 = 0x5555;
+/// Page header: values were moved to the other page, which is written last with `0x0000`.
+const OBSOLETE: u16 = 0x0000;
+
+/// Record marker: written last, to discard records torn by a reset.
+const VALID: u16 = 0x0000;
+
+/// Size of the page header in bytes.
+const HEADER_SIZE: u32 = 8;
+/// Size of a record in bytes: key, value low, value high, marker.
+const RECORD_SIZE: u32 = 8;
+/// Number of records per page.
+const RECORDS: u32 = (PAGE_SIZE - HEADER_SIZE) / RECORD_SIZE;
+
+/// Wear leveled key-value storage, on two reserved flash pages.
+///
+/// Each write appends a record to the active page. When the page is full, the latest value of
+/// each key is moved to the other page. This spreads the erases, of which a page endures about
+/// 10k, over many writes. A reset during a write keeps the previous value.
+///
+/// The pages must not be used by the program. For the last two pages of the 64KB flash, shrink
+/// the `FLASH` length in `memory.x` to `62K`, and use:
+///
+/// ```
+/// let mut storage = flash::Storage::new(0x0800_F800)?;
+/// storage.write_u32(1, calibration)?;
+/// let calibration = storage.read_u32(1).unwrap_or(DEFAULT_CALIBRATION);
+/// ```
+#[derive(Debug)]
+pub struct Storage {
+    /// Start address of the two pages.
+    pages: [u32; 2],
+    /// Index of the active page.
+    active: usize,
+    /// Index of the next free record in the active page.
+    next: u32,
+}
+
+impl Storage {
+    /// Storage on the two pages from `first_page`, which must be page aligned.
+    ///
+    /// Erases the pages if they hold no active page. Returns [Error::Address] if the pages are
+    /// not aligned, or not inside the flash.
+    pub fn new(first_page: u32) -> Result<Self, Error> {
+        let inside = first_page >= START && first_page - START + 2 * PAGE_SIZE <= size();
+        if first_page % PAGE_SIZE != 0 || !inside {
+            return Err(Error::Address);
+        }
+        let pages = [first_page, first_page + PAGE_SIZE];
+        let headers = pages.map(read_halfword);
+        let active = match headers {
+            [ACTIVE, ACTIVE] => {
+                // Reset during a move: the page that is not full received the values last.
+                if record_key(pages[0], RECORDS - 1) == ERASED {
+                    0
+                } else {
+                    1
+                }
+            }
+            [ACTIVE, _] => 0,
+            [_, ACTIVE] => 1,
+            _ => {
+                let mut flash = Flash::unlock();
+                flash.erase_page(pages[1])?;
+                format(&mut flash, pages[0])?;
+                0
+            }
+        };
+        let next = (0..RECORDS)
+            .find(|&i| record_key(pages[active], i) == ERASED)
+            .unwrap_or(RECORDS);
+        Ok(Self {
+            pages,
+            active,
+            next,
+        })
+    }
+
+    /// Latest value written to `key`.
+    pub fn read_u32(&self, key: u16) -> Option<u32> {
+        let page = self.pages[self.active];
+        (0..self.next)
+            .rev()
+            .find(|&i| record_key(page, i) == key && is_valid(page, i))
+            .map(|i| record_value(page, i))
+    }
+
+    /// Store a value for `key`, any key except `0xFFFF`.
+    ///
+    /// Does not write if the value is unchanged.
+    pub fn write_u32(&mut self, key: u16, value: u32) -> Result<(), Error> {
+        assert!(key != ERASED, "Key 0xFFFF is reserved");
+        if self.read_u32(key) == Some(value) {
+            return Ok(());
+        }
+        let mut flash = Flash::unlock();
+        if self.next == RECORDS {
+            return self.move_values(&mut flash, key, value);
+        }
+        write_record(&mut flash, self.pages[self.active], self.next, key, value)?;
+        self.next += 1;
+        Ok(())
+    }
+
+    /// Move the latest value of each key to the other page, with `value` as the new value of
+    /// `key`.
+    ///
+    /// The new value is written before the other page is marked active, so a reset keeps either
+    /// the old or the new value.
+    fn move_values(&mut self, flash: &mut Flash, key: u16, value: u32) -> Result<(), Error> {
+        let from = self.pages[self.active];
+        let to = self.pages[1 - self.active];
+        flash.erase_page(to)?;
+        let mut next = 0;
+        for i in 0..RECORDS {
+            let old = record_key(from, i);
+            // Copy each key once, from its latest record.
+            let latest = (i + 1..RECORDS).all(|j| record_key(from, j) != old || !is_valid(from, j));
+            if old == key || !is_valid(from, i) || !latest {
+                continue;
+            }
+            // Leave room for the new value, and keep the last record erased to tell the new page
+            // apart after a reset.
+            if next == RECORDS - 2 {
+                return Err(Error::Full);
+            }
+            write_record(flash, to, next, old, record_value(from, i))?;
+            next += 1;
+        }
+        write_record(flash, to, next, key, value)?;
+        next += 1;
+        flash.write_halfword(to, ACTIVE)?;
+        flash.write_halfword(from, OBSOLETE)?;
+        flash.erase_page(from)?;
+        self.active = 1 - self.active;
+        self.next = next;
+        Ok(())
+    }
+}
+
+/// Erase a page, and mark it active.
+fn format(flash: &mut Flash, page: u32) -> Result<(), Error> {
+    flash.erase_page(page)?;
+    flash.write_halfword(page, ACTIVE)
+}
+
+/// Read a halfword of the pages, which [Storage::new] checked to be inside the flash.
+#[inline]
+fn read_halfword(addr: u32) -> u16 {
+    unsafe { ptr::read_volatile(addr as *const u16) }
+}
+
+/// Address of record `i` in a page.
+#[inline]
+fn record_addr(page: u32, i: u32) -> u32 {
+    page + HEADER_SIZE + i * RECORD_SIZE
+}
+
+#[inline]
+fn record_key(page: u32, i: u32) -> u16 {
+    read_halfword(record_addr(page, i))
+}
+
+#[inline]
+fn record_value(page: u32, i: u32) -> u32 {
+    let addr = record_addr(page, i);
+    read_halfword(addr + 2) as u32 | (read_halfword(addr + 4) as u32) << 16
+}
+
+#[inline]
+fn is_valid(page: u32, i: u32) -> bool {
+    read_halfword(record_addr(page, i) + 6) == VALID
+}
+
+fn write_record(flash: &mut Flash, page: u32, i: u32, key: u16, value: u32) -> Result<(), Error> {
+    let addr = record_addr(page, i);
+    flash.write_halfword(addr, key)?;
+    flash.write_halfword(addr + 2, value as u16)?;
+    flash.write_halfword(addr + 4, (value >> 16) as u16)?;
+    flash.write_halfword(addr + 6, VALID)
+}
@@ -44,6 +44,19 @@ pub fn configure(pin: Gpio, mode: Mode) {
     pac::configure(pin.0, pin.1, mode);
 }
 
+/// Configure several GPIO pins with the same mode.
+///
+/// ```
+/// const CAN_ID: [gpio::Gpio; 3] = [gpio::PC15, gpio::PC14, gpio::PA15];
+/// gpio::configure_all(&CAN_ID, gpio::Mode::InputPullUp);
+/// ```
+#[inline]
+pub fn configure_all(pins: &[Gpio], mode: Mode) {
+    for &pin in pins {
+        configure(pin, mode);
+    }
+}
+
 /// Set the GPIO pin value.
 ///
 /// Assumes pin was [configured][configure] as [output][OutputMode] before calling this.
@@ -52,12 +65,88 @@ pub fn write(pin: Gpio, value: bool) {
     pac::write(pin.0, pin.1, value)
 }
 
+/// Set several pins of a port at once.
+///
+/// Bit `n` of `value` is written to pin `n`, for the pins selected by `mask`. Other pins are left
+/// untouched. All pins change on the same clock edge.
+///
+/// ```
+/// // PB12 high, PB13 and PB14 low.
+/// gpio::write(gpio::PB15, true);
+/// gpio::write_port_masked(gpio::Port::B, 0b0111 << 12, 0b0001 << 12);
+/// assert!(gpio::read_output(gpio::PB12));
+/// assert!(!gpio::read_output(gpio::PB13));
+/// assert!(!gpio::read_output(gpio::PB14));
+/// // Outside the mask.
+/// assert!(gpio::read_output(gpio::PB15));
+/// ```
+///
+/// Assumes the pins were [configured][configure] as [output][OutputMode] before calling this.
+#[inline]
+pub fn write_port_masked(port: Port, mask: u16, value: u16) {
+    pac::write_masked(port, mask, value)
+}
+
+/// Set the value of several GPIO pins.
+///
+/// ```
+/// const LEDS: [gpio::Gpio; 3] = [gpio::PB5, gpio::PA10, gpio::PB15];
+/// gpio::configure_all(&LEDS, gpio::Mode::OuputPushPull(gpio::Speed::Max2MHz));
+/// gpio::write_all(&LEDS, true);
+/// ```
+///
+/// Assumes pins were [configured][configure] as [output][OutputMode] before calling this.
+#[inline]
+pub fn write_all(pins: &[Gpio], value: bool) {
+    for &pin in pins {
+        write(pin, value);
+    }
+}
+
+/// Flip the GPIO pin output value.
+///
+/// Assumes pin was [configured][configure] as [output][OutputMode] before calling this.
+/// Only the pin itself is written, so it is safe to use from interrupt context, as long as the
+/// same pin is not written concurrently.
+#[inline]
+pub fn toggle(pin: Gpio) {
+    pac::toggle(pin.0, pin.1)
+}
+
 /// Read the GPIO pin value.
 #[inline]
 pub fn read(pin: Gpio) -> bool {
     pac::read(pin.0, pin.1)
 }
 
+/// Read the GPIO pin output value.
+///
+/// Returns the last value [written][write()], from the output data register, regardless of the
+/// pin mode. Unlike [read], which returns the level on the pad, e.g. pulled low on an open-drain
+/// output.
+#[inline]
+pub fn read_output(pin: Gpio) -> bool {
+    pac::read_output(pin.0, pin.1)
+}
+
+/// Lock the configuration of the GPIO pin until the next reset.
+///
+/// Once locked, calls to [configure] for that pin silently have no effect. The pin value can
+/// still be written.
+///
+/// The lock register of a port freezes after the first lock sequence, so only one call per port
+/// takes effect: further pins of the same port can not be locked afterwards.
+#[inline]
+pub fn lock(pin: Gpio) {
+    pac::lock(pin.0, 1 << pin.1 as u8)
+}
+
+/// Returns whether the configuration of the GPIO pin is [locked][lock()].
+#[inline]
+pub fn is_locked(pin: Gpio) -> bool {
+    pac::locked(pin.0) & (1 << pin.1 as u8) > 0
+}
+
 /// Enable the alternate function IO peripheral.
 #[inline]
 pub fn enable_alternate_function_io() {
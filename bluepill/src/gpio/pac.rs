@@ -102,19 +102,42 @@ pub(crate) fn configure(port: Port, pin: Pin, mode: Mode) {
 /// Sets the pin value.
 ///
 /// Assumes the pin was configured as output mode.
+///
+/// Uses a single store to `BSRR`, which is atomic with respect to other pins on the port.
 #[inline]
 pub(crate) fn write(port: Port, pin: Pin, value: bool) {
+    // Lower half sets pins, upper half resets pins.
+    let bit = if value { 1 << pin as u8 } else { 1 << (pin as u8 + 16) };
+    unsafe {
+        (*port.ptr()).bsrr.write(|w| w.bits(bit));
+    }
+}
+
+/// Sets the masked pins of the port, using a single store to `BSRR`.
+#[inline]
+pub(crate) fn write_masked(port: Port, mask: u16, value: u16) {
+    let set = (value & mask) as u32;
+    let reset = (!value & mask) as u32;
     unsafe {
-        (*port.ptr()).odr.modify(|r, w| {
-            w.bits(if value {
-                r.bits() | (1 << pin as u8)
-            } else {
-                r.bits() & !(1 << pin as u8)
-            })
-        });
+        (*port.ptr()).bsrr.write(|w| w.bits(reset << 16 | set));
     }
 }
 
+/// Flip the pin output value.
+///
+/// Reads `ODR`, and writes the inverse using a single store to `BSRR`.
+#[inline]
+pub(crate) fn toggle(port: Port, pin: Pin) {
+    let odr = unsafe { (*port.ptr()).odr.read().bits() };
+    write(port, pin, odr & (1 << pin as u8) == 0);
+}
+
+/// Read the pin output value, from `ODR`.
+#[inline]
+pub(crate) fn read_output(port: Port, pin: Pin) -> bool {
+    let value = unsafe { (*port.ptr()).odr.read().bits() };
+    (value & (1 << pin as u8)) > 0
+}
 
 /// Read the pin value.
 #[inline]
@@ -122,3 +145,32 @@ pub(crate) fn read(port: Port, pin: Pin) -> bool {
     let value = unsafe { (*port.ptr()).idr.read().bits() };
     (value & (1 << pin as u8)) > 0
 }
+
+/// Lock the configuration of the masked pins, using the `LCKR` lock sequence.
+#[inline]
+pub(crate) fn lock(port: Port, mask: u16) {
+    let key = 1 << 16;
+    let bits = mask as u32;
+    unsafe {
+        let lckr = &(*port.ptr()).lckr;
+        lckr.write(|w| w.bits(key | bits));
+        lckr.write(|w| w.bits(bits));
+        lckr.write(|w| w.bits(key | bits));
+        // Reading completes the sequence, the second read confirms the lock.
+        lckr.read();
+        lckr.read();
+    }
+}
+
+/// Read the configuration lock bits of the port.
+///
+/// Only valid once the lock key is active.
+#[inline]
+pub(crate) fn locked(port: Port) -> u16 {
+    let lckr = unsafe { (*port.ptr()).lckr.read().bits() };
+    if lckr & (1 << 16) > 0 {
+        lckr as u16
+    } else {
+        0
+    }
+}
@@ -15,12 +15,32 @@
 //!
 //! // Write data to device.
 //! let data = [3, 4];
-//! bus.write(who_am_i, register, &data);
+//! bus.write(who_am_i, register, &data)?;
 //! ```
+//!
+//! Transfers return an [Error] instead of hanging if a device does not respond.
+//!
+//! For other pins than the hardware I2C pins, use the bit-banged [SoftBus].
 
 mod pac;
+mod soft;
 
-pub use pac::{I2c, Map1, Register, Speed, WhoAmI};
+pub use pac::{Address, I2c, Map1, Register, Speed, WhoAmI, WhoAmI10};
+pub use soft::SoftBus;
+
+/// I2C transfer errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A status flag was not set in time.
+    Timeout,
+    /// Address or data byte was not acknowledged.
+    Nack,
+    /// Misplaced START or STOP condition.
+    BusError,
+    /// Another master took the bus.
+    ArbitrationLost,
+}
 
 /// Master I2C bus.
 ///
@@ -37,6 +57,7 @@ impl Bus {
         i2c.set_speed(speed);
         i2c.configure_gpio();
         i2c.enable();
+        trace!("i2c: configured");
         Self { i2c }
     }
 
@@ -48,38 +69,85 @@ impl Bus {
 
     /// Write multiple bytes to [Register] of device with id [WhoAmI].
     #[inline]
-    pub fn write(&mut self, address: WhoAmI, register: impl Register, data: &[u8]) {
-        self.i2c.master_transmit_data(address, register, data, true);
+    pub fn write(
+        &mut self,
+        address: impl Into<Address>,
+        register: impl Register,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.i2c
+            .master_transmit_data(address.into(), register, data, true)
     }
 
     /// Read multiple bytes from [Register] of device with id [WhoAmI].
+    ///
+    /// Uses a repeated START between writing the register and reading the data, so the bus is not
+    /// released in between.
     #[inline]
-    pub fn read(&self, address: WhoAmI, register: impl Register, data: &mut [u8]) {
-        self.i2c.master_transmit_data(address, register, &[], false);
-        self.read_direct(address, data);
+    pub fn read(
+        &self,
+        address: impl Into<Address>,
+        register: impl Register,
+        data: &mut [u8],
+    ) -> Result<(), Error> {
+        self.i2c
+            .master_write_then_read(address.into(), register, data)
     }
 
     /// Read multiple bytes from device with id [WhoAmI], without specifying the register.
     ///
     /// Some simple devices have only one register to read from, in which case it is often ommited.
     #[inline]
-    pub fn read_direct(&self, address: WhoAmI, data: &mut [u8]) {
-        self.i2c.master_receive_data(address, data);
+    pub fn read_direct(&self, address: impl Into<Address>, data: &mut [u8]) -> Result<(), Error> {
+        self.i2c.master_receive_data(address.into(), data)
     }
 
     /// Read [Register] value from device with id [WhoAmI].
     #[inline]
-    pub fn read_single(&self, address: WhoAmI, register: impl Register) -> u8 {
+    pub fn read_single(
+        &self,
+        address: impl Into<Address>,
+        register: impl Register,
+    ) -> Result<u8, Error> {
         let mut data = [0u8];
-        self.read(address, register, &mut data);
-        data[0]
+        self.read(address.into(), register, &mut data)?;
+        Ok(data[0])
     }
 
     /// Write byte to [Register] of device with id [WhoAmI].
     #[inline]
-    pub fn write_single(&mut self, address: WhoAmI, register: impl Register, value: u8) {
+    pub fn write_single(
+        &mut self,
+        address: impl Into<Address>,
+        register: impl Register,
+        value: u8,
+    ) -> Result<(), Error> {
         self.i2c
-            .master_transmit_data(address, register, &[value], true);
+            .master_transmit_data(address.into(), register, &[value], true)
+    }
+
+    /// Returns the 7-bit addresses of all devices that acknowledge, in the range `0x08..0x78`.
+    ///
+    /// ```
+    /// for address in bus.scan() {
+    ///     debug_println!("Found device at {:#04x}", address);
+    /// }
+    /// ```
+    pub fn scan(&self) -> impl Iterator<Item = u8> + '_ {
+        (0x08..0x78).filter(move |&address| self.i2c.probe(WhoAmI(address).into()).is_ok())
+    }
+
+    /// Free the bus if a slave holds SDA low, e.g. after a reset in the middle of a transfer.
+    ///
+    /// Disables the peripheral, clocks SCL until SDA is released and generates a STOP, before
+    /// enabling the peripheral again. Returns whether the bus was freed.
+    pub fn recover(&mut self) -> bool {
+        self.i2c.disable();
+        let freed = self.i2c.recover_gpio();
+        self.i2c.configure_gpio();
+        self.i2c.enable();
+        trace!("i2c: recovered = {}", freed);
+        freed
     }
 
     /// Disable peripheral.
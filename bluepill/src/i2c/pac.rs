@@ -1,9 +1,15 @@
-use crate::gpio;
+use super::Error;
+use crate::{clock, delay, gpio};
 use crate::gpio::{PB10, PB11, PB6, PB7, PB8, PB9};
 use stm32f1xx_hal::pac::{
-    i2c1::RegisterBlock as Ptr, Peripherals as DevicePeripherals, I2C1, I2C2,
+    i2c1::sr1, i2c1::RegisterBlock as Ptr, Peripherals as DevicePeripherals, I2C1, I2C2,
 };
 
+/// Number of status register reads before a transfer times out.
+///
+/// Roughly 10ms at 72MHz, enough for a few bytes at 100kHz.
+const TIMEOUT: u32 = 100_000;
+
 /// Regsiter controlled by the [I2C bus][super::Bus].
 pub trait Register {
     fn adress(self) -> u8;
@@ -19,15 +25,48 @@ pub trait Register {
 #[derive(Copy, Clone, Debug)]
 pub struct WhoAmI(pub u8);
 
-impl WhoAmI {
+/// 10-bit device address.
+///
+/// Only the 10 lsb bits are used. Few devices use 10-bit addressing, their datasheet lists an
+/// address above `0x7F`.
+///
+/// ```
+/// bus.write(WhoAmI10(0x2A5), register, &data)?;
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct WhoAmI10(pub u16);
+
+/// Device address, either 7-bit ([WhoAmI]) or 10-bit ([WhoAmI10]).
+#[derive(Copy, Clone, Debug)]
+pub enum Address {
+    Seven(u8),
+    Ten(u16),
+}
+
+impl From<WhoAmI> for Address {
     #[inline]
-    pub(crate) fn transmit_address(&self) -> u8 {
-        self.0 << 1
+    fn from(who_am_i: WhoAmI) -> Self {
+        Self::Seven(who_am_i.0)
     }
+}
 
+impl From<WhoAmI10> for Address {
     #[inline]
-    pub(crate) fn receive_address(&self) -> u8 {
-        self.0 << 1 | 1
+    fn from(who_am_i: WhoAmI10) -> Self {
+        Self::Ten(who_am_i.0)
+    }
+}
+
+impl Address {
+    /// First byte after START: the 7-bit address, or the `11110xx` header of a 10-bit address,
+    /// followed by the read bit.
+    #[inline]
+    pub(super) fn first_byte(self, read: bool) -> u8 {
+        let address = match self {
+            Self::Seven(address) => address & 0x7F,
+            Self::Ten(address) => 0b1111_0000 >> 1 | ((address >> 8) & 0b11) as u8,
+        };
+        address << 1 | read as u8
     }
 }
 
@@ -70,11 +109,12 @@ impl I2c {
         }
     }
 
+    /// Returns the (SCL, SDA) pins, and sets the matching remap.
     #[inline]
-    pub(crate) fn configure_gpio(&self) {
+    fn remap_pins(&self) -> (gpio::Gpio, gpio::Gpio) {
         unsafe {
             let dp = DevicePeripherals::steal();
-            let (scl, sda) = match self {
+            match self {
                 Self::I2C1(map) => {
                     match map {
                         Map1::PB6_PB7 => {
@@ -90,16 +130,54 @@ impl I2c {
                     }
                 }
                 Self::I2C2 => (PB10, PB11),
-            };
-            gpio::configure(
-                scl,
-                gpio::Mode::AlternateFunctionOutputOpenDrain(gpio::Speed::Max50MHz),
-            );
-            gpio::configure(
-                sda,
-                gpio::Mode::AlternateFunctionOutputOpenDrain(gpio::Speed::Max50MHz),
-            );
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn configure_gpio(&self) {
+        let (scl, sda) = self.remap_pins();
+        gpio::configure(
+            scl,
+            gpio::Mode::AlternateFunctionOutputOpenDrain(gpio::Speed::Max50MHz),
+        );
+        gpio::configure(
+            sda,
+            gpio::Mode::AlternateFunctionOutputOpenDrain(gpio::Speed::Max50MHz),
+        );
+    }
+
+    /// Free a bus held low by a slave, by clocking SCL manually.
+    ///
+    /// The peripheral must be disabled. Returns whether SDA was released.
+    pub(crate) fn recover_gpio(&self) -> bool {
+        let (scl, sda) = self.remap_pins();
+        let mode = gpio::Mode::OutputOpenDrain(gpio::Speed::Max2MHz);
+        gpio::write_all(&[scl, sda], true);
+        gpio::configure_all(&[scl, sda], mode);
+
+        // Clock up to 9 pulses at ~100kHz, until the slave releases SDA.
+        for _ in 0..9 {
+            if gpio::read(sda) {
+                break;
+            }
+            gpio::write(scl, false);
+            delay::micros(5);
+            gpio::write(scl, true);
+            delay::micros(5);
         }
+
+        // Generate STOP: SDA rises while SCL is high.
+        gpio::write(scl, false);
+        delay::micros(5);
+        gpio::write(sda, false);
+        delay::micros(5);
+        gpio::write(scl, true);
+        delay::micros(5);
+        gpio::write(sda, true);
+        delay::micros(5);
+
+        gpio::read(sda)
     }
 
     #[inline]
@@ -115,19 +193,19 @@ impl I2c {
         unsafe {
             // i2c frequency = 100kHz.
             let fclk: u32 = 100_000;
-            // Set peripheral clock to 8MHz.
-            let pclk = 8_000_000;
+            // Peripheral clock must match the actual APB1 clock.
+            let pclk = clock::apb1_speed();
             (*self.ptr())
                 .cr2
                 .modify(|_, w| w.freq().bits((pclk / 1_000_000) as u8));
-            // Set i2c clock frequency: ccr / pclk = 1 / ( 2 * fclk )
-            let ccr = pclk / (fclk * 2);
+            // Set i2c clock frequency: ccr / pclk = 1 / ( 2 * fclk ), rounded up.
+            let ccr = pclk.div_ceil(fclk * 2).max(4);
             (*self.ptr()).ccr.write(|w| w.ccr().bits(ccr as u16)); // Overwrite register.
-            // Set rise time.
-            let trise = 10 * fclk;
+
+            // Set rise time: maximum 1000ns.
             (*self.ptr())
                 .trise
-                .modify(|_, w| w.trise().bits((pclk / trise) as u8 + 1));
+                .modify(|_, w| w.trise().bits((pclk / 1_000_000) as u8 + 1));
         }
     }
 
@@ -136,27 +214,27 @@ impl I2c {
         unsafe {
             // i2c frequency = 400kHz.
             let fclk: u32 = 400_000;
-            // Set peripheral clock to 20MHz (must be multiple of 10MHz).
-            let pclk = 20_000_000;
+            // Peripheral clock must match the actual APB1 clock.
+            let pclk = clock::apb1_speed();
             (*self.ptr())
                 .cr2
                 .modify(|_, w| w.freq().bits((pclk / 1_000_000) as u8));
-            // Set i2c clock frequency:
-            // Th = 9 * ccr / pclk
-            // Tl = 16 * ccr / pclk
-            // fclk = 1 / ( Tl + Th ) = pclk / 25 / ccr
-            // ccr = pclk / 25 / fclk
+            // Set i2c clock frequency, with duty cycle Tl / Th = 2:
+            // Th = ccr / pclk
+            // Tl = 2 * ccr / pclk
+            // fclk = 1 / ( Tl + Th ) = pclk / 3 / ccr
+            // ccr = pclk / 3 / fclk, rounded up.
+            // The 16/9 duty cycle would require pclk to be a multiple of 10MHz.
             let fmode = 1 << 15;
-            let duty = 1 << 14;
-            let ccr = pclk / (25 * fclk);
+            let ccr = pclk.div_ceil(3 * fclk).max(1);
             (*self.ptr())
                 .ccr
-                .write(|w| w.ccr().bits((ccr | (fmode | duty)) as u16)); // Overwrite register.
-            // Set rise time.
-            let trise = 10 * fclk;
+                .write(|w| w.ccr().bits((ccr | fmode) as u16)); // Overwrite register.
+
+            // Set rise time: maximum 300ns.
             (*self.ptr())
                 .trise
-                .modify(|_, w| w.trise().bits((pclk / trise) as u8 + 1));
+                .modify(|_, w| w.trise().bits((pclk / 1_000_000 * 3 / 10) as u8 + 1));
         }
     }
 
@@ -179,81 +257,202 @@ impl I2c {
         unsafe { (*self.ptr()).sr2.read().busy().bit_is_set() }
     }
 
+    /// Wait until `done` holds for SR1, checking for errors.
+    ///
+    /// Gives up after [TIMEOUT] status reads.
     #[inline]
-    pub(crate) fn master_transmit_data(
-        &self,
-        adress: WhoAmI,
-        register: impl Register,
-        data: &[u8],
-        stop: bool,
-    ) {
+    fn wait(&self, done: impl Fn(&sr1::R) -> bool) -> Result<(), Error> {
+        for _ in 0..TIMEOUT {
+            let sr1 = unsafe { (*self.ptr()).sr1.read() };
+            if sr1.af().bit_is_set() {
+                return Err(Error::Nack);
+            }
+            if sr1.berr().bit_is_set() {
+                return Err(Error::BusError);
+            }
+            if sr1.arlo().bit_is_set() {
+                return Err(Error::ArbitrationLost);
+            }
+            if done(&sr1) {
+                return Ok(());
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Release the bus after an error: write STOP, and clear the error flags.
+    #[inline]
+    fn abort(&self, error: Error) -> Error {
         unsafe {
-            // Activate Acknowledge.
-            (*self.ptr()).cr1.modify(|_, w| w.ack().ack());
+            (*self.ptr()).cr1.modify(|_, w| w.stop().stop());
+            (*self.ptr()).sr1.modify(|_, w| {
+                w.af().clear_bit();
+                w.berr().clear_bit();
+                w.arlo().clear_bit()
+            });
+        }
+        self.wait_stop();
+        error
+    }
 
+    /// Send START followed by the address, and wait for the device to acknowledge.
+    ///
+    /// Does not clear the ADDR flag. A 10-bit read address is sent as the write address, followed
+    /// by a repeated START and the header with the read bit set.
+    #[inline]
+    fn start(&self, adress: Address, read: bool) -> Result<(), Error> {
+        unsafe {
             // Transmit START condition.
             // Automatically switches to MASTER mode.
             (*self.ptr()).cr1.modify(|_, w| w.start().set_bit());
 
             // Read SR1 to check completion of START transmission.
-            while !(*self.ptr()).sr1.read().sb().bit_is_set() {}
+            self.wait(|sr1| sr1.sb().bit_is_set())?;
+
+            if let Address::Ten(address) = adress {
+                // Write header, and wait for ADD10.
+                (*self.ptr())
+                    .dr
+                    .write(|w| w.dr().bits(adress.first_byte(false)));
+                self.wait(|sr1| sr1.add10().bit_is_set())?;
+                // Write low address byte.
+                (*self.ptr()).dr.write(|w| w.dr().bits(address as u8));
+                if !read {
+                    return self.wait(|sr1| sr1.addr().bit_is_set());
+                }
+                self.wait(|sr1| sr1.addr().bit_is_set())?;
+                let _ = (*self.ptr()).sr2.read().bits();
+                // Repeated START, for the read header.
+                (*self.ptr()).cr1.modify(|_, w| w.start().set_bit());
+                self.wait(|sr1| sr1.sb().bit_is_set())?;
+            }
+
             // Write slave adress.
             (*self.ptr())
                 .dr
-                .write(|w| w.dr().bits(adress.transmit_address()));
+                .write(|w| w.dr().bits(adress.first_byte(read)));
 
             // Read SR1 to check ADDRESS transmission completion.
-            while !(*self.ptr()).sr1.read().addr().bit_is_set() {}
+            self.wait(|sr1| sr1.addr().bit_is_set())
+        }
+    }
+
+    /// Send START and the write address, and check whether the device acknowledges.
+    #[inline]
+    pub(crate) fn probe(&self, adress: Address) -> Result<(), Error> {
+        self.try_probe(adress).map_err(|error| self.abort(error))
+    }
+
+    #[inline]
+    fn try_probe(&self, adress: Address) -> Result<(), Error> {
+        // Acknowledged if ADDR is set, otherwise AF is set.
+        self.start(adress, false)?;
+        unsafe {
+            let _ = (*self.ptr()).sr2.read().bits();
+            (*self.ptr()).cr1.modify(|_, w| w.stop().stop());
+        }
+        self.wait_stop();
+        Ok(())
+    }
+
+    /// Wait until the STOP condition has been sent, before a new START can be written.
+    #[inline]
+    fn wait_stop(&self) {
+        for _ in 0..TIMEOUT {
+            if unsafe { (*self.ptr()).cr1.read().stop().bit_is_clear() } {
+                return;
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn master_transmit_data(
+        &self,
+        adress: Address,
+        register: impl Register,
+        data: &[u8],
+        stop: bool,
+    ) -> Result<(), Error> {
+        self.try_master_transmit_data(adress, register, data, stop)
+            .map_err(|error| self.abort(error))
+    }
+
+    #[inline]
+    fn try_master_transmit_data(
+        &self,
+        adress: Address,
+        register: impl Register,
+        data: &[u8],
+        stop: bool,
+    ) -> Result<(), Error> {
+        unsafe {
+            // Activate Acknowledge.
+            (*self.ptr()).cr1.modify(|_, w| w.ack().ack());
+
+            // Transmit START condition and slave adress.
+            self.start(adress, false)?;
             // Read SR2 to activate data transmission.
             let _ = (*self.ptr()).sr2.read().bits();
 
             // Write first register byte.
             // Read SR1 to check if the transmission buffer is empty (TxE).
-            while !(*self.ptr()).sr1.read().tx_e().is_empty() {}
+            self.wait(|sr1| sr1.tx_e().is_empty())?;
             // Write data to DR.
             (*self.ptr()).dr.write(|w| w.dr().bits(register.adress()));
 
             // Write data to DR
             for byte in data.iter() {
                 // Read SR1 to check if the transmission buffer is empty (TxE).
-                while !(*self.ptr()).sr1.read().tx_e().is_empty() {}
+                self.wait(|sr1| sr1.tx_e().is_empty())?;
                 // Write data to DR.
                 (*self.ptr()).dr.write(|w| w.dr().bits(*byte));
             }
 
             // Wait until byte transfer is complete (BTF).
-            while !(*self.ptr()).sr1.read().btf().is_finished() {}
+            self.wait(|sr1| sr1.btf().is_finished())?;
 
             // Write STOP condition, unless repeated start follows.
             if stop {
                 (*self.ptr()).cr1.modify(|_, w| w.stop().stop());
             }
         }
+        Ok(())
+    }
+
+    /// Write the register address, followed by a repeated START and reading `data`.
+    ///
+    /// The bus is not released in between.
+    #[inline]
+    pub(crate) fn master_write_then_read(
+        &self,
+        adress: Address,
+        register: impl Register,
+        data: &mut [u8],
+    ) -> Result<(), Error> {
+        let stop = data.is_empty();
+        self.try_master_transmit_data(adress, register, &[], stop)
+            .and_then(|()| self.try_master_receive_data(adress, data))
+            .map_err(|error| self.abort(error))
     }
 
     #[inline]
-    pub(crate) fn master_receive_data(&self, adress: WhoAmI, data: &mut [u8]) {
+    pub(crate) fn master_receive_data(&self, adress: Address, data: &mut [u8]) -> Result<(), Error> {
+        self.try_master_receive_data(adress, data)
+            .map_err(|error| self.abort(error))
+    }
+
+    #[inline]
+    fn try_master_receive_data(&self, adress: Address, data: &mut [u8]) -> Result<(), Error> {
         let len = data.len();
         if len == 0 {
-            return;
+            return Ok(());
         }
         unsafe {
             // Activate Acknowledge.
             (*self.ptr()).cr1.modify(|_, w| w.ack().ack());
 
-            // Transmit START condition.
-            // Automatically switches to MASTER mode.
-            (*self.ptr()).cr1.modify(|_, w| w.start().set_bit());
-
-            // Read SR1 to check completion of START transmission.
-            while !(*self.ptr()).sr1.read().sb().bit_is_set() {}
-            // Write slave adress.
-            (*self.ptr())
-                .dr
-                .write(|w| w.dr().bits(adress.receive_address()));
-
-            // Read SR1 to check ADDRESS transmission completion.
-            while !(*self.ptr()).sr1.read().addr().bit_is_set() {}
+            // Transmit START condition and slave adress.
+            self.start(adress, true)?;
             // Read SR2 to activate data transmission.
             let _ = (*self.ptr()).sr2.read().bits();
 
@@ -266,7 +465,7 @@ impl I2c {
             // Read data from DR
             for (i, byte) in data.iter_mut().enumerate() {
                 // Read SR1 to check if the receiver buffer is not empty (RxNE)
-                while (*self.ptr()).sr1.read().rx_ne().is_empty() {}
+                self.wait(|sr1| sr1.rx_ne().is_not_empty())?;
                 // Transmit Non-Acknowledge (NA) after reading second to last RxNE.
                 if (len - 1) == (i + 1) {
                     (*self.ptr()).cr1.modify(|_, w| w.ack().nak());
@@ -276,5 +475,6 @@ impl I2c {
                 *byte = (*self.ptr()).dr.read().dr().bits()
             }
         }
+        Ok(())
     }
 }
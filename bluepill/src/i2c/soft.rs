@@ -0,0 +1,229 @@
+use super::{Address, Error, Register, Speed, WhoAmI};
+use crate::{delay, gpio};
+
+/// Longest time a slave may stretch the clock, in microseconds.
+const STRETCH_TIMEOUT_US: u32 = 10_000;
+
+/// Bit-banged master I2C bus, on any two GPIO pins.
+///
+/// Offers the same transfers as the hardware [Bus][super::Bus], e.g. for when the hardware I2C
+/// pins are taken. Both pins are open-drain outputs, so the bus needs external pull-ups.
+///
+/// ```
+/// let mut bus = i2c::SoftBus::new(gpio::PA4, gpio::PA5, i2c::Speed::Std100kHz);
+/// let value = bus.read_single(WhoAmI(0x68), DeviceRegister(2))?;
+/// ```
+///
+/// Supports clock stretching, but not multiple masters. Transfers block, and are slower than
+/// the configured speed due to the GPIO overhead.
+pub struct SoftBus {
+    scl: gpio::Gpio,
+    sda: gpio::Gpio,
+    /// Half of the SCL period in microseconds.
+    half_period_us: u32,
+}
+
+impl SoftBus {
+    /// Configure the pins as open-drain outputs, and release the bus.
+    pub fn new(scl: gpio::Gpio, sda: gpio::Gpio, speed: Speed) -> Self {
+        let mode = gpio::Mode::OutputOpenDrain(gpio::Speed::Max10MHz);
+        gpio::write_all(&[scl, sda], true);
+        gpio::configure_all(&[scl, sda], mode);
+        let half_period_us = match speed {
+            Speed::Std100kHz => 5,
+            Speed::Fast400kHz => 1,
+        };
+        Self {
+            scl,
+            sda,
+            half_period_us,
+        }
+    }
+
+    /// Write multiple bytes to [Register] of device with id [WhoAmI].
+    pub fn write(
+        &mut self,
+        address: impl Into<Address>,
+        register: impl Register,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let address = address.into();
+        self.transfer(|bus| {
+            bus.begin(address, false)?;
+            bus.write_byte(register.adress())?;
+            data.iter().try_for_each(|&byte| bus.write_byte(byte))
+        })
+    }
+
+    /// Read multiple bytes from [Register] of device with id [WhoAmI].
+    ///
+    /// Uses a repeated START between writing the register and reading the data.
+    pub fn read(
+        &self,
+        address: impl Into<Address>,
+        register: impl Register,
+        data: &mut [u8],
+    ) -> Result<(), Error> {
+        let address = address.into();
+        self.transfer(|bus| {
+            bus.begin(address, false)?;
+            bus.write_byte(register.adress())?;
+            bus.begin(address, true)?;
+            bus.read_bytes(data)
+        })
+    }
+
+    /// Read multiple bytes from device with id [WhoAmI], without specifying the register.
+    pub fn read_direct(&self, address: impl Into<Address>, data: &mut [u8]) -> Result<(), Error> {
+        let address = address.into();
+        self.transfer(|bus| {
+            bus.begin(address, true)?;
+            bus.read_bytes(data)
+        })
+    }
+
+    /// Read [Register] value from device with id [WhoAmI].
+    pub fn read_single(
+        &self,
+        address: impl Into<Address>,
+        register: impl Register,
+    ) -> Result<u8, Error> {
+        let mut data = [0u8];
+        self.read(address, register, &mut data)?;
+        Ok(data[0])
+    }
+
+    /// Write byte to [Register] of device with id [WhoAmI].
+    pub fn write_single(
+        &mut self,
+        address: impl Into<Address>,
+        register: impl Register,
+        value: u8,
+    ) -> Result<(), Error> {
+        self.write(address, register, &[value])
+    }
+
+    /// Returns the 7-bit addresses of all devices that acknowledge, in the range `0x08..0x78`.
+    pub fn scan(&self) -> impl Iterator<Item = u8> + '_ {
+        (0x08..0x78).filter(move |&address| {
+            self.transfer(|bus| bus.begin(WhoAmI(address).into(), false))
+                .is_ok()
+        })
+    }
+
+    /// Run a transfer, and end it with a STOP, also on errors.
+    fn transfer<T>(&self, f: impl FnOnce(&Self) -> Result<T, Error>) -> Result<T, Error> {
+        let result = f(self);
+        self.stop();
+        result
+    }
+
+    /// Send a (repeated) START, followed by the address.
+    fn begin(&self, address: Address, read: bool) -> Result<(), Error> {
+        self.start()?;
+        match address {
+            Address::Seven(_) => self.write_byte(address.first_byte(read)),
+            Address::Ten(ten) => {
+                self.write_byte(address.first_byte(false))?;
+                self.write_byte(ten as u8)?;
+                if read {
+                    // Header again, with the read bit.
+                    self.start()?;
+                    self.write_byte(address.first_byte(true))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Read bytes, acknowledging all but the last.
+    fn read_bytes(&self, data: &mut [u8]) -> Result<(), Error> {
+        let len = data.len();
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self.read_byte(i + 1 < len)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn wait(&self) {
+        delay::micros(self.half_period_us);
+    }
+
+    /// Release SCL, and wait while a slave stretches the clock.
+    fn release_scl(&self) -> Result<(), Error> {
+        gpio::write(self.scl, true);
+        for _ in 0..STRETCH_TIMEOUT_US {
+            if gpio::read(self.scl) {
+                return Ok(());
+            }
+            delay::micros(1);
+        }
+        Err(Error::Timeout)
+    }
+
+    /// START: SDA falls while SCL is high.
+    fn start(&self) -> Result<(), Error> {
+        gpio::write(self.sda, true);
+        self.wait();
+        self.release_scl()?;
+        self.wait();
+        gpio::write(self.sda, false);
+        self.wait();
+        gpio::write(self.scl, false);
+        Ok(())
+    }
+
+    /// STOP: SDA rises while SCL is high.
+    fn stop(&self) {
+        gpio::write(self.scl, false);
+        gpio::write(self.sda, false);
+        self.wait();
+        let _ = self.release_scl();
+        self.wait();
+        gpio::write(self.sda, true);
+        self.wait();
+    }
+
+    fn write_bit(&self, bit: bool) -> Result<(), Error> {
+        gpio::write(self.sda, bit);
+        self.wait();
+        self.release_scl()?;
+        self.wait();
+        gpio::write(self.scl, false);
+        Ok(())
+    }
+
+    fn read_bit(&self) -> Result<bool, Error> {
+        gpio::write(self.sda, true);
+        self.wait();
+        self.release_scl()?;
+        let bit = gpio::read(self.sda);
+        self.wait();
+        gpio::write(self.scl, false);
+        Ok(bit)
+    }
+
+    /// Write a byte, msb first, and check for the acknowledge.
+    fn write_byte(&self, byte: u8) -> Result<(), Error> {
+        for i in (0..8).rev() {
+            self.write_bit((byte >> i) & 1 > 0)?;
+        }
+        // Acknowledge pulls SDA low.
+        if self.read_bit()? {
+            Err(Error::Nack)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read a byte, msb first, and acknowledge if `ack`.
+    fn read_byte(&self, ack: bool) -> Result<u8, Error> {
+        let mut byte = 0;
+        for _ in 0..8 {
+            byte = byte << 1 | self.read_bit()? as u8;
+        }
+        self.write_bit(!ack)?;
+        Ok(byte)
+    }
+}
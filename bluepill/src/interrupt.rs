@@ -0,0 +1,57 @@
+//! Interrupt priorities.
+//!
+//! Example usage:
+//!
+//! ```
+//! // Let the timer interrupt preempt the USART interrupts.
+//! interrupt::set_priority(Interrupt::TIM3, interrupt::Priority::new(1));
+//! interrupt::set_priority(Interrupt::USART2, interrupt::Priority::new(2));
+//! ```
+
+use stm32f1xx_hal::pac::Interrupt;
+
+/// Number of implemented priority bits.
+pub const PRIORITY_BITS: u8 = 4;
+
+/// Interrupt priority level.
+///
+/// Lower levels have higher priority: `0` is the most urgent, `15` the least.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Priority(u8);
+
+impl Priority {
+    /// Highest priority level.
+    pub const HIGHEST: Self = Self(0);
+
+    /// Lowest priority level.
+    pub const LOWEST: Self = Self((1 << PRIORITY_BITS) - 1);
+
+    /// Priority level, clamped to the implemented range `0..=15`.
+    #[inline]
+    pub fn new(level: u8) -> Self {
+        Self(level.min(Self::LOWEST.0))
+    }
+
+    /// Priority level.
+    #[inline]
+    pub fn level(self) -> u8 {
+        self.0
+    }
+
+    /// Value of the NVIC priority register, where only the msb bits are implemented.
+    #[inline]
+    fn register(self) -> u8 {
+        self.0 << (8 - PRIORITY_BITS)
+    }
+}
+
+/// Set the priority of the interrupt.
+///
+/// Changing priorities can break priority based critical sections, so do this during setup.
+#[inline]
+pub fn set_priority(interrupt: Interrupt, priority: Priority) {
+    unsafe {
+        let mut cp = cortex_m::Peripherals::steal();
+        cp.NVIC.set_priority(interrupt, priority.register());
+    }
+}
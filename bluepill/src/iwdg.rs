@@ -0,0 +1,83 @@
+//! Independent watchdog.
+//!
+//! Resets the microcontroller if it is not fed in time. Clocked from the internal ~40kHz LSI
+//! oscillator, so it keeps running even if the system clock fails.
+//!
+//! Example usage:
+//!
+//! ```
+//! let mut watchdog = iwdg::Iwdg::start(100);
+//!
+//! loop {
+//!     // Main loop must complete within 100ms.
+//!     watchdog.feed();
+//! }
+//! ```
+//!
+//! Once started, the watchdog can not be stopped, except by a reset.
+
+use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
+
+/// Nominal LSI clock speed in Hertz.
+///
+/// The actual frequency varies between 30kHz and 60kHz, so the timeout is approximate.
+pub const LSI_SPEED: u32 = 40_000;
+
+/// Longest configurable timeout in milliseconds.
+pub const MAX_TIMEOUT_MS: u32 = 256 * 4096 * 1_000 / LSI_SPEED;
+
+/// Key values written to the key register.
+const KEY_RELOAD: u16 = 0xAAAA;
+const KEY_UNLOCK: u16 = 0x5555;
+const KEY_START: u16 = 0xCCCC;
+
+/// Independent watchdog.
+///
+/// Use [start][Iwdg::start()] to start the watchdog.
+pub struct Iwdg {}
+
+impl Iwdg {
+    /// Start the watchdog with the given timeout.
+    ///
+    /// The timeout is clamped to [MAX_TIMEOUT_MS].
+    pub fn start(timeout_ms: u32) -> Self {
+        let (pr, rl) = prescaler_and_reload(timeout_ms);
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            // Starting the watchdog also enables the LSI.
+            dp.IWDG.kr.write(|w| w.key().bits(KEY_START));
+            // Enable write access to the prescaler and reload registers.
+            dp.IWDG.kr.write(|w| w.key().bits(KEY_UNLOCK));
+            dp.IWDG.pr.write(|w| w.pr().bits(pr));
+            dp.IWDG.rlr.write(|w| w.rl().bits(rl));
+            // Wait for the registers to be updated in the LSI clock domain.
+            while dp.IWDG.sr.read().pvu().bit_is_set() || dp.IWDG.sr.read().rvu().bit_is_set() {}
+            dp.IWDG.kr.write(|w| w.key().bits(KEY_RELOAD));
+        }
+        Self {}
+    }
+
+    /// Reload the watchdog counter.
+    #[inline]
+    pub fn feed(&mut self) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            dp.IWDG.kr.write(|w| w.key().bits(KEY_RELOAD));
+        }
+    }
+}
+
+/// Returns the (prescaler, reload) register values.
+///
+/// Uses the smallest prescaler for which the reload value fits in 12 bits.
+fn prescaler_and_reload(timeout_ms: u32) -> (u8, u16) {
+    let timeout_ms = timeout_ms.min(MAX_TIMEOUT_MS);
+    for pr in 0..7 {
+        let div = 4 << pr;
+        let ticks = timeout_ms * (LSI_SPEED / 1_000) / div;
+        if ticks <= 4096 {
+            return (pr as u8, ticks.max(1) as u16 - 1);
+        }
+    }
+    (6, 4095)
+}
@@ -1,44 +1,118 @@
 use crate::gpio;
+use crate::timer::pwm;
 
 /// Led controller.
 ///
-/// Assumes led is on when pin is low.
+/// [Led::new] assumes led is on when pin is low, as for the onboard led.
 #[derive(Debug)]
 pub struct Led {
-    pin: gpio::Gpio,
-    on: bool,
+    output: Output,
+    /// Zero if off, [u16::MAX] if fully on.
+    brightness: u16,
+    /// Led is on when pin is low.
+    active_low: bool,
+    /// Active blink pattern.
+    blink: Option<Blink>,
+}
+
+/// Blink pattern state, see [Led::blink_pattern].
+#[derive(Debug)]
+struct Blink {
+    pattern: u32,
+    ticks_per_phase: u32,
+    ticks: u32,
+    /// Bit of the pattern that is displayed.
+    phase: u32,
+}
+
+/// Pin driving the led.
+#[derive(Debug)]
+enum Output {
+    Pin(gpio::Gpio),
+    Pwm(pwm::Channel),
 }
 
 impl Led {
+    /// Blink pattern: always on.
+    pub const SOLID: u32 = u32::MAX;
+    /// Blink pattern: on for half the period.
+    pub const SLOW: u32 = 0x0000_FFFF;
+    /// Blink pattern: two short pulses.
+    pub const HEARTBEAT: u32 = 0b11_0011;
+    /// Blink pattern: one short pulse.
+    pub const BLIMP: u32 = 1;
+
     #[inline]
     pub fn new(pin: gpio::Gpio, mode: gpio::OutputMode) -> Self {
-        let led = Self { pin, on: false };
+        Self::new_with_polarity(pin, mode, true)
+    }
+
+    /// Led controller for an led that is on when the pin is low if `active_low`, or high
+    /// otherwise.
+    #[inline]
+    pub fn new_with_polarity(pin: gpio::Gpio, mode: gpio::OutputMode, active_low: bool) -> Self {
+        let mut led = Self {
+            output: Output::Pin(pin),
+            brightness: 0,
+            active_low,
+            blink: None,
+        };
         led.update();
         gpio::configure(pin, mode.into());
         led
     }
 
+    /// Led controller with dimming, driven by a [configured][pwm::Channel::configure()] PWM
+    /// channel.
+    ///
+    /// The polarity is set by the channel configuration.
     #[inline]
-    fn update(&self) {
-        gpio::write(self.pin, !self.on);
+    pub fn new_pwm(channel: pwm::Channel) -> Self {
+        let mut led = Self {
+            output: Output::Pwm(channel),
+            brightness: 0,
+            active_low: false,
+            blink: None,
+        };
+        led.update();
+        led
     }
 
     #[inline]
-    pub fn on(&mut self) {
-        self.on = true;
+    fn update(&mut self) {
+        match &mut self.output {
+            Output::Pin(pin) => gpio::write(*pin, (self.brightness > 0) != self.active_low),
+            Output::Pwm(channel) => {
+                // Full brightness requires a compare value above the auto reload value.
+                let period = channel.read_arr() as u32 + 1;
+                let ccr = self.brightness as u32 * period / u16::MAX as u32;
+                channel.write_ccr(ccr.min(u16::MAX as u32) as u16);
+            }
+        }
+    }
+
+    /// Set the brightness, from off at zero to fully on at [u16::MAX].
+    ///
+    /// Without PWM, the led is on for any nonzero level.
+    #[inline]
+    pub fn set_brightness(&mut self, level: u16) {
+        self.brightness = level;
         self.update();
     }
 
+    #[inline]
+    pub fn on(&mut self) {
+        self.set_brightness(u16::MAX);
+    }
+
     #[inline]
     pub fn off(&mut self) {
-        self.on = false;
-        self.update();
+        self.set_brightness(0);
     }
 
     #[inline]
     pub fn toggle(&mut self) {
-        self.on = !self.on;
-        self.update();
+        self.write(self.brightness == 0);
     }
 
     #[inline]
@@ -49,4 +123,60 @@ impl Led {
             self.off();
         }
     }
+
+    /// Blink the led with a pattern, advanced by calling [tick][Led::tick()].
+    ///
+    /// Each bit of the pattern is a phase of `ticks_per_phase` ticks, starting at the least
+    /// significant bit. The led is on during phases with the bit set. Setting the active pattern
+    /// again does not restart it, so this can be called every loop.
+    ///
+    /// ```
+    /// let mut led = Led::new(gpio::PC13, gpio::OutputMode::PushPull(gpio::Speed::Max2MHz));
+    /// // Period of 32 * 10ms.
+    /// led.blink_pattern(Led::HEARTBEAT, 10);
+    ///
+    /// loop {
+    ///     led.tick();
+    ///     delay::millis(1);
+    /// }
+    /// ```
+    pub fn blink_pattern(&mut self, pattern: u32, ticks_per_phase: u32) {
+        if let Some(blink) = &mut self.blink {
+            if blink.pattern == pattern {
+                blink.ticks_per_phase = ticks_per_phase;
+                return;
+            }
+        }
+        self.blink = Some(Blink {
+            pattern,
+            ticks_per_phase,
+            ticks: 0,
+            phase: 0,
+        });
+        self.write(pattern & 1 > 0);
+    }
+
+    /// Stop blinking, leaving the led in its current state.
+    #[inline]
+    pub fn stop_blink(&mut self) {
+        self.blink = None;
+    }
+
+    /// Advance the [blink pattern][Led::blink_pattern()], call from a periodic timer.
+    ///
+    /// Overrides the led state set by [write][Led::write()] at the start of each phase.
+    pub fn tick(&mut self) {
+        let blink = match &mut self.blink {
+            Some(blink) => blink,
+            None => return,
+        };
+        blink.ticks += 1;
+        if blink.ticks < blink.ticks_per_phase {
+            return;
+        }
+        blink.ticks = 0;
+        blink.phase = (blink.phase + 1) % 32;
+        let on = (blink.pattern >> blink.phase) & 1 > 0;
+        self.write(on);
+    }
 }
@@ -6,15 +6,42 @@
 
 #![no_std]
 
+#[macro_use]
+mod macros;
+
+pub mod adc;
+pub mod bitband;
+pub mod bkp;
+pub mod can;
 pub mod clock;
+pub mod crc;
 pub mod delay;
+pub mod device;
+pub mod dma;
+pub mod flash;
 pub mod gpio;
 pub mod i2c;
-pub mod usb;
+pub mod interrupt;
+pub mod iwdg;
+pub mod log;
+pub mod power;
+pub mod profile;
+pub mod rtc;
 pub mod spi;
+pub mod time;
 pub mod timer;
 pub mod uart;
+pub mod usb;
 
 mod led;
 
+#[cfg(feature = "critical-section")]
+mod critical_section_impl;
+#[cfg(feature = "defmt")]
+pub mod defmt_logger;
+#[cfg(feature = "panic")]
+pub mod panic;
+#[cfg(feature = "rtt")]
+pub mod rtt;
+
 pub use led::Led;
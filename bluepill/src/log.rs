@@ -0,0 +1,39 @@
+//! Semihosting logging.
+//!
+//! Example usage:
+//!
+//! ```
+//! // Prints only in debug builds.
+//! debug_println!("Counter = {}", counter);
+//! ```
+//!
+//! Semihosting halts the core for every write, and hangs without a debugger attached.
+
+use core::fmt;
+use cortex_m_semihosting::hio;
+
+/// Writer to the semihosting standard output of the debugger.
+pub struct Semihosting;
+
+impl fmt::Write for Semihosting {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        hio::hstdout()
+            .map_err(|_| fmt::Error)?
+            .write_all(s.as_bytes())
+            .map_err(|_| fmt::Error)
+    }
+}
+
+/// Print a formatted line over semihosting, in debug builds only.
+///
+/// Expands to nothing when `debug_assertions` are disabled.
+#[macro_export]
+macro_rules! debug_println {
+    ($($arg:tt)*) => {
+        if cfg!(debug_assertions) {
+            use core::fmt::Write;
+            let _ = writeln!($crate::log::Semihosting, $($arg)*);
+        }
+    };
+}
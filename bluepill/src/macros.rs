@@ -0,0 +1,17 @@
+//! Internal logging macros.
+//!
+//! Forward to `defmt` if the `defmt` feature is enabled, and expand to nothing otherwise.
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::trace!($($arg)*);
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::warn!($($arg)*);
+    };
+}
@@ -0,0 +1,49 @@
+//! Panic handler, enabled by the `panic` feature.
+//!
+//! Blinks SOS on the panic led, [PC13][gpio::PC13] by default, forever.
+//!
+//! Example usage:
+//!
+//! ```
+//! // Use a different led (assumed on when low).
+//! bluepill::panic::set_led(gpio::PB12);
+//! ```
+//!
+//! Do not combine with another panic handler crate, such as `panic-halt`.
+
+use crate::{delay, gpio, Led};
+use core::cell::Cell;
+use core::panic::PanicInfo;
+use cortex_m::interrupt::{self, Mutex};
+
+/// Duration of a single dot in milliseconds.
+const DOT_MS: u32 = 150;
+
+/// SOS: on-durations in dots.
+const PATTERN: [u32; 9] = [1, 1, 1, 3, 3, 3, 1, 1, 1];
+
+static LED: Mutex<Cell<gpio::Gpio>> = Mutex::new(Cell::new(gpio::PC13));
+
+/// Set the led that signals a panic.
+#[inline]
+pub fn set_led(pin: gpio::Gpio) {
+    interrupt::free(|cs| LED.borrow(cs).set(pin));
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    interrupt::disable();
+    let pin = interrupt::free(|cs| LED.borrow(cs).get());
+    gpio::enable();
+    let mut led = Led::new(pin, gpio::OutputMode::PushPull(gpio::Speed::Max2MHz));
+    loop {
+        for &dots in PATTERN.iter() {
+            led.on();
+            delay::millis(dots * DOT_MS);
+            led.off();
+            delay::millis(DOT_MS);
+        }
+        // Word gap.
+        delay::millis(7 * DOT_MS);
+    }
+}
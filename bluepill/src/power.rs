@@ -0,0 +1,273 @@
+//! Low power modes.
+//!
+//! Example usage:
+//!
+//! ```
+//! // Wake up on the timer update interrupt.
+//! unsafe { NVIC::unmask(Interrupt::TIM3) };
+//! timer::TIM3.update_interrupt_enable();
+//!
+//! loop {
+//!     power::sleep();
+//!     // Handle the work signalled by the interrupt.
+//! }
+//! ```
+
+use cortex_m::peripheral::NVIC;
+use stm32f1xx_hal::pac::{Interrupt, Peripherals as DevicePeripherals};
+
+/// EXTI line connected to the PVD output.
+const PVD_EXTI_LINE: u32 = 16;
+
+/// Instruction used to enter stop mode.
+#[derive(Copy, Clone, Debug)]
+pub enum StopEntry {
+    /// Wait for interrupt: wake up on an EXTI line configured as interrupt.
+    Wfi,
+    /// Wait for event: wake up on an EXTI line configured as event.
+    Wfe,
+}
+
+/// Programmable voltage detector threshold.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug)]
+pub enum PvdLevel {
+    V2_2 = 0,
+    V2_3 = 1,
+    V2_4 = 2,
+    V2_5 = 3,
+    V2_6 = 4,
+    V2_7 = 5,
+    V2_8 = 6,
+    V2_9 = 7,
+}
+
+/// Enter sleep mode until the next interrupt.
+///
+/// The core clock stops, while peripherals keep running. Any unmasked interrupt wakes up the
+/// core, e.g. a periodic [timer][crate::timer] update, a USART receive, or the USB
+/// (`USB_LP_CAN_RX0`) interrupt. Without an enabled interrupt, this blocks forever.
+#[inline]
+pub fn sleep() {
+    cortex_m::asm::wfi();
+}
+
+/// Enter stop mode.
+///
+/// All clocks in the 1.8V domain stop, SRAM and registers are retained. The voltage regulator
+/// is put in low power mode.
+///
+/// Only an EXTI line can wake up the device, e.g. a GPIO interrupt, or the USB wakeup
+/// (EXTI line 18). On wake up the HSI is used as system clock, so [clock::init][crate::clock::init]
+/// must be called again.
+pub fn stop(entry: StopEntry) {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        let mut cp = cortex_m::Peripherals::steal();
+        dp.RCC.apb1enr.modify(|_, w| w.pwren().enabled());
+        dp.PWR.cr.modify(|_, w| {
+            // Stop mode, instead of standby, when entering deep sleep.
+            w.pdds().clear_bit();
+            // Regulator in low power mode.
+            w.lpds().set_bit()
+        });
+        cp.SCB.set_sleepdeep();
+        match entry {
+            StopEntry::Wfi => cortex_m::asm::wfi(),
+            StopEntry::Wfe => cortex_m::asm::wfe(),
+        }
+        // Plain sleep for the next wait instruction.
+        cp.SCB.clear_sleepdeep();
+    }
+}
+
+/// Enter standby mode.
+///
+/// The 1.8V domain is powered off: SRAM and registers are lost, except for the backup domain.
+/// The device exits standby through a reset, triggered by a rising edge on the
+/// [WKUP pin][enable_wakeup_pin], an RTC alarm, the independent watchdog, or the `NRST` pin.
+///
+/// Use [woke_from_standby] after the reset to detect a standby wake up.
+pub fn standby() -> ! {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        let mut cp = cortex_m::Peripherals::steal();
+        dp.RCC.apb1enr.modify(|_, w| w.pwren().enabled());
+        dp.PWR.cr.modify(|_, w| {
+            // Standby mode when entering deep sleep.
+            w.pdds().set_bit();
+            // Clear the wakeup flag, else standby is exited immediately.
+            w.cwuf().set_bit()
+        });
+        cp.SCB.set_sleepdeep();
+    }
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+/// Enable or disable wake up from standby on a rising edge of the WKUP pin (PA0).
+///
+/// When enabled, PA0 is forced to input pull-down, regardless of its GPIO configuration.
+#[inline]
+pub fn enable_wakeup_pin(enable: bool) {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.apb1enr.modify(|_, w| w.pwren().enabled());
+        dp.PWR.csr.modify(|_, w| w.ewup().bit(enable));
+    }
+}
+
+/// Returns whether the device was reset when waking up from standby, and clears the flag.
+#[inline]
+pub fn woke_from_standby() -> bool {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.apb1enr.modify(|_, w| w.pwren().enabled());
+        let standby = dp.PWR.csr.read().sbf().bit_is_set();
+        dp.PWR.cr.modify(|_, w| w.csbf().set_bit());
+        standby
+    }
+}
+
+/// Enable the programmable voltage detector, with the `PVD` interrupt.
+///
+/// The interrupt triggers when VDD drops below the threshold, e.g. to shut down motors before a
+/// brown-out reset. The interrupt handler must call [clear_pvd_interrupt].
+///
+/// ```
+/// power::enable_pvd(power::PvdLevel::V2_9);
+///
+/// #[interrupt]
+/// fn PVD() {
+///     power::clear_pvd_interrupt();
+///     if power::pvd_below_threshold() {
+///         // Supply is collapsing.
+///     }
+/// }
+/// ```
+pub fn enable_pvd(threshold: PvdLevel) {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.apb1enr.modify(|_, w| w.pwren().enabled());
+        dp.PWR.cr.modify(|_, w| w.pls().bits(threshold as u8));
+        dp.PWR.cr.modify(|_, w| w.pvde().set_bit());
+        // PVDO rises when VDD drops below the threshold.
+        dp.EXTI
+            .rtsr
+            .modify(|r, w| w.bits(r.bits() | 1 << PVD_EXTI_LINE));
+        dp.EXTI
+            .imr
+            .modify(|r, w| w.bits(r.bits() | 1 << PVD_EXTI_LINE));
+        clear_pvd_interrupt();
+        NVIC::unmask(Interrupt::PVD);
+    }
+}
+
+/// Disable the programmable voltage detector, and its interrupt.
+pub fn disable_pvd() {
+    NVIC::mask(Interrupt::PVD);
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.EXTI
+            .imr
+            .modify(|r, w| w.bits(r.bits() & !(1 << PVD_EXTI_LINE)));
+        dp.PWR.cr.modify(|_, w| w.pvde().clear_bit());
+    }
+}
+
+/// Returns whether VDD is below the PVD threshold.
+#[inline]
+pub fn pvd_below_threshold() -> bool {
+    unsafe { DevicePeripherals::steal().PWR.csr.read().pvdo().bit_is_set() }
+}
+
+/// Clear the pending PVD interrupt.
+#[inline]
+pub fn clear_pvd_interrupt() {
+    unsafe {
+        DevicePeripherals::steal()
+            .EXTI
+            .pr
+            .write(|w| w.bits(1 << PVD_EXTI_LINE));
+    }
+}
+
+/// Enable the power and backup interface clocks, and unlock the backup domain for writing.
+///
+/// Required before writing the RTC, the LSE configuration or the [backup registers][crate::bkp].
+/// Without it, those writes are silently ignored. The domain stays unlocked; use
+/// [BackupDomainGuard] to unlock it temporarily instead.
+#[inline]
+pub fn enable_backup_domain() {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC
+            .apb1enr
+            .modify(|_, w| w.pwren().enabled().bkpen().enabled());
+        dp.PWR.cr.modify(|_, w| w.dbp().set_bit());
+    }
+}
+
+/// Reset the backup domain, e.g. to select another [RTC clock source][crate::rtc::ClockSource].
+///
+/// # Note
+///
+/// This erases the [backup registers][crate::bkp], stops the LSE, and clears the RTC counter and
+/// configuration. Requires the backup domain to be [unlocked][enable_backup_domain].
+#[inline]
+pub fn reset_backup_domain() {
+    unsafe {
+        let dp = DevicePeripherals::steal();
+        dp.RCC.bdcr.modify(|_, w| w.bdrst().set_bit());
+        dp.RCC.bdcr.modify(|_, w| w.bdrst().clear_bit());
+    }
+}
+
+/// Unlocks the backup domain for writing, and locks it again when dropped.
+///
+/// If the domain was already unlocked, e.g. by [enable_backup_domain], it stays unlocked.
+///
+/// ```
+/// {
+///     let _unlocked = power::BackupDomainGuard::new();
+///     bkp::write(0, 42);
+/// }
+/// // Backup domain is write protected again.
+/// ```
+pub struct BackupDomainGuard {
+    /// Whether the domain was unlocked before.
+    was_unlocked: bool,
+}
+
+impl BackupDomainGuard {
+    #[inline]
+    pub fn new() -> Self {
+        // Reads as locked while the power interface clock is off.
+        let was_unlocked = unsafe { DevicePeripherals::steal().PWR.cr.read().dbp().bit_is_set() };
+        enable_backup_domain();
+        Self { was_unlocked }
+    }
+}
+
+impl Default for BackupDomainGuard {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for BackupDomainGuard {
+    #[inline]
+    fn drop(&mut self) {
+        if self.was_unlocked {
+            return;
+        }
+        unsafe {
+            DevicePeripherals::steal()
+                .PWR
+                .cr
+                .modify(|_, w| w.dbp().clear_bit());
+        }
+    }
+}
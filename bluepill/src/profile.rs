@@ -0,0 +1,58 @@
+//! Cycle count profiling, using the DWT cycle counter.
+//!
+//! Example usage:
+//!
+//! ```
+//! profile::init();
+//!
+//! let t = profile::start();
+//! spi_bus.write(register, &data);
+//! let cycles = t.elapsed();
+//! ```
+//!
+//! The counter wraps every ~60s at 72MHz, so longer durations can not be measured.
+
+use crate::clock;
+use cortex_m::peripheral::DWT;
+
+/// Enable the DWT cycle counter.
+///
+/// Only needs to be called once. The counter stops while the core is halted by a debugger.
+#[inline]
+pub fn init() {
+    unsafe {
+        let mut cp = cortex_m::Peripherals::steal();
+        cp.DCB.enable_trace();
+        cp.DWT.enable_cycle_counter();
+    }
+}
+
+/// Current value of the cycle counter.
+#[inline]
+pub fn cycles() -> u32 {
+    DWT::cycle_count()
+}
+
+/// Start of a measurement.
+#[inline]
+pub fn start() -> Timestamp {
+    Timestamp(cycles())
+}
+
+/// Cycle counter value at the start of a measurement.
+#[derive(Copy, Clone, Debug)]
+pub struct Timestamp(u32);
+
+impl Timestamp {
+    /// Core clock cycles passed since the start.
+    #[inline]
+    pub fn elapsed(self) -> u32 {
+        cycles().wrapping_sub(self.0)
+    }
+
+    /// Microseconds passed since the start, at the [active core clock][clock::hclk_hz()].
+    #[inline]
+    pub fn elapsed_micros(self) -> u32 {
+        self.elapsed() / (clock::hclk_hz() / 1_000_000).max(1)
+    }
+}
@@ -0,0 +1,160 @@
+//! Real time clock.
+//!
+//! The RTC is a 32-bit seconds counter in the backup domain. With a backup battery on `VBAT` it
+//! keeps counting across resets and power loss.
+//!
+//! Example usage:
+//!
+//! ```
+//! let mut rtc = rtc::Rtc::new(rtc::ClockSource::Lse)?;
+//!
+//! // Set the wall-clock time once, e.g. received from a host.
+//! rtc.set_unix(1_672_531_200);
+//!
+//! let now: u32 = rtc.now_unix();
+//! ```
+
+use crate::{clock, power};
+use stm32f1xx_hal::pac::Peripherals as DevicePeripherals;
+
+/// RTC clock source.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockSource {
+    /// External `32.768kHz` crystal, keeps running on the backup battery.
+    Lse,
+    /// Internal ~40kHz oscillator, inaccurate and stops without main power.
+    Lsi,
+}
+
+impl ClockSource {
+    /// Value of the `RTCSEL` bits.
+    #[inline]
+    fn rtcsel(self) -> u8 {
+        match self {
+            Self::Lse => 1,
+            Self::Lsi => 2,
+        }
+    }
+
+    #[inline]
+    fn speed(self) -> u32 {
+        match self {
+            Self::Lse => clock::LSE_SPEED,
+            Self::Lsi => clock::LSI_SPEED,
+        }
+    }
+}
+
+/// RTC setup error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The RTC runs from another clock source, which can only be changed by a
+    /// [backup domain reset][power::reset_backup_domain()].
+    OtherClockSource,
+}
+
+/// Real time clock, counting seconds.
+pub struct Rtc {}
+
+impl Rtc {
+    /// Unlock the backup domain, and start the RTC at one tick per second.
+    ///
+    /// If the RTC is already running from the given source, e.g. after a reset with a backup
+    /// battery, the counter is left untouched.
+    ///
+    /// # Note
+    ///
+    /// Fails if another clock source is selected. Switching requires a
+    /// [backup domain reset][power::reset_backup_domain()], which also erases the
+    /// [backup registers][crate::bkp], so it is left to the caller.
+    pub fn new(source: ClockSource) -> Result<Self, Error> {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+
+            // Enable write access to the backup domain.
+            power::enable_backup_domain();
+
+            match source {
+                ClockSource::Lse => clock::enable_lse(),
+                ClockSource::Lsi => clock::enable_lsi(),
+            }
+
+            let bdcr = dp.RCC.bdcr.read();
+            let running = bdcr.rtcen().bit_is_set() && bdcr.rtcsel().bits() == source.rtcsel();
+            if !running {
+                // The clock source can only be changed after a backup domain reset.
+                let rtcsel = bdcr.rtcsel().bits();
+                if rtcsel != 0 && rtcsel != source.rtcsel() {
+                    return Err(Error::OtherClockSource);
+                }
+                dp.RCC.bdcr.modify(|_, w| {
+                    w.rtcsel().bits(source.rtcsel());
+                    w.rtcen().set_bit()
+                });
+
+                let rtc = Self {};
+                rtc.wait_sync();
+                // Prescaler: one tick per second.
+                let prl = source.speed() - 1;
+                rtc.configure(|| {
+                    dp.RTC.prlh.write(|w| w.prlh().bits((prl >> 16) as u8));
+                    dp.RTC.prll.write(|w| w.prll().bits(prl as u16));
+                });
+            }
+        }
+        let rtc = Self {};
+        rtc.wait_sync();
+        Ok(rtc)
+    }
+
+    /// Set the counter to the given unix time in seconds.
+    pub fn set_unix(&mut self, secs: u32) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            self.configure(|| {
+                dp.RTC.cnth.write(|w| w.cnth().bits((secs >> 16) as u16));
+                dp.RTC.cntl.write(|w| w.cntl().bits(secs as u16));
+            });
+        }
+    }
+
+    /// Current unix time in seconds.
+    pub fn now_unix(&self) -> u32 {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            // Re-read if the low half overflowed in between.
+            loop {
+                let high = dp.RTC.cnth.read().bits();
+                let low = dp.RTC.cntl.read().bits();
+                if high == dp.RTC.cnth.read().bits() {
+                    return (high << 16) | low;
+                }
+            }
+        }
+    }
+
+    /// Wait for the RTC registers to be synchronized with the APB1 clock.
+    ///
+    /// Required after a reset, or after the APB1 clock was stopped, before reading the counter.
+    fn wait_sync(&self) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            dp.RTC.crl.modify(|_, w| w.rsf().clear_bit());
+            while dp.RTC.crl.read().rsf().bit_is_clear() {}
+        }
+    }
+
+    /// Write RTC registers in configuration mode.
+    fn configure(&self, write: impl FnOnce()) {
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            // Wait for the last write operation to finish.
+            while dp.RTC.crl.read().rtoff().bit_is_clear() {}
+            dp.RTC.crl.modify(|_, w| w.cnf().set_bit());
+            write();
+            dp.RTC.crl.modify(|_, w| w.cnf().clear_bit());
+            while dp.RTC.crl.read().rtoff().bit_is_clear() {}
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! Minimal RTT (Real-Time Transfer) logging, enabled by the `rtt` feature.
+//!
+//! Provides a single up channel, which is read by the debug probe in the background, e.g. by
+//! `probe-rs` or J-Link RTT Viewer. Unlike semihosting, writing does not halt the core.
+//!
+//! Example usage:
+//!
+//! ```
+//! rtt::init();
+//! rtt_println!("Counter = {}", counter);
+//! ```
+//!
+//! Output which does not fit in the buffer is dropped.
+
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+use cortex_m::interrupt;
+
+/// Size of the up channel buffer in bytes.
+pub const BUFFER_SIZE: usize = 1024;
+
+/// RTT channel descriptor, as expected by the host.
+#[repr(C)]
+struct Channel {
+    name: *const u8,
+    buffer: *mut u8,
+    size: u32,
+    write: u32,
+    read: u32,
+    flags: u32,
+}
+
+/// RTT control block, as expected by the host.
+#[repr(C)]
+struct ControlBlock {
+    id: [u8; 16],
+    max_up_channels: u32,
+    max_down_channels: u32,
+    up: Channel,
+    down: Channel,
+}
+
+/// Located by the host by scanning RAM for the identifier, which is set by [init].
+#[no_mangle]
+static mut _SEGGER_RTT: ControlBlock = ControlBlock {
+    id: [0; 16],
+    max_up_channels: 1,
+    max_down_channels: 1,
+    up: Channel {
+        name: ptr::null(),
+        buffer: ptr::null_mut(),
+        size: 0,
+        write: 0,
+        read: 0,
+        flags: 0,
+    },
+    down: Channel {
+        name: ptr::null(),
+        buffer: ptr::null_mut(),
+        size: 0,
+        write: 0,
+        read: 0,
+        flags: 0,
+    },
+};
+
+static mut BUFFER: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
+
+const ID: &[u8; 16] = b"SEGGER RTT\0\0\0\0\0\0";
+const NAME: &[u8] = b"Terminal\0";
+
+/// Initialize the control block.
+///
+/// Must be called before writing, otherwise output is dropped.
+pub fn init() {
+    interrupt::free(|_| unsafe {
+        let cb = ptr::addr_of_mut!(_SEGGER_RTT);
+        (*cb).up.name = NAME.as_ptr();
+        (*cb).up.buffer = ptr::addr_of_mut!(BUFFER) as *mut u8;
+        (*cb).up.size = BUFFER_SIZE as u32;
+        (*cb).up.write = 0;
+        (*cb).up.read = 0;
+        // The identifier is written last, so the host never finds a partially initialized block.
+        compiler_fence(Ordering::SeqCst);
+        ptr::write_volatile(ptr::addr_of_mut!((*cb).id), *ID);
+    });
+}
+
+/// Write bytes to the up channel, without blocking.
+///
+/// Returns the number of bytes written.
+pub fn write_bytes(bytes: &[u8]) -> usize {
+    interrupt::free(|_| unsafe {
+        let up = ptr::addr_of_mut!(_SEGGER_RTT.up);
+        let size = (*up).size as usize;
+        if size == 0 {
+            return 0;
+        }
+        let read = ptr::read_volatile(ptr::addr_of!((*up).read)) as usize;
+        let mut write = ptr::read_volatile(ptr::addr_of!((*up).write)) as usize;
+        let mut count = 0;
+        for &byte in bytes {
+            let next = (write + 1) % size;
+            if next == read {
+                break;
+            }
+            ptr::write_volatile((*up).buffer.add(write), byte);
+            write = next;
+            count += 1;
+        }
+        compiler_fence(Ordering::SeqCst);
+        ptr::write_volatile(ptr::addr_of_mut!((*up).write), write as u32);
+        count
+    })
+}
+
+/// Writer to the RTT up channel.
+pub struct Writer;
+
+impl fmt::Write for Writer {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Print a formatted line to the RTT up channel.
+#[macro_export]
+macro_rules! rtt_println {
+    ($($arg:tt)*) => {{
+        use core::fmt::Write;
+        let _ = writeln!($crate::rtt::Writer, $($arg)*);
+    }};
+}
@@ -11,6 +11,7 @@
 //!     speed: 1_000_000,
 //!     mode: spi::Mode::Mode0,
 //!     byteorder: spi::ByteOrder::MsbFirst,
+//!     ..Default::default()
 //! }.make(spi::Spi2);
 //!
 //! // Fake device register.
@@ -23,10 +24,10 @@
 
 mod pac;
 
-pub use pac::{ByteOrder, Master, Mode, Port, Spi};
+pub use pac::{ByteOrder, FrameSize, Master, Mode, Port, Spi};
 
 use crate::delay::micros;
-use crate::gpio;
+use crate::{dma, gpio};
 use cortex_m::interrupt;
 
 /// Register controlled by the [spi bus][Bus].
@@ -52,6 +53,23 @@ pub struct Config {
     pub mode: Mode,
     /// Byte order: lsb or msb first.
     pub byteorder: ByteOrder,
+    /// Data frame size.
+    ///
+    /// In 16-bit mode, the byte oriented methods send each byte as a zero padded frame. Use
+    /// [write16][Bus::write16()] and [read16][Bus::read16()] for full frames.
+    pub frame: FrameSize,
+}
+
+impl Default for Config {
+    /// 1MHz, mode 0, msb first, 8-bit frames.
+    fn default() -> Self {
+        Self {
+            speed: 1_000_000,
+            mode: Mode::Mode0,
+            byteorder: ByteOrder::MsbFirst,
+            frame: FrameSize::Bits8,
+        }
+    }
 }
 
 impl Config {
@@ -61,12 +79,15 @@ impl Config {
     }
 }
 
-/// Master SPI bus.
+/// SPI bus.
 ///
-/// Does not support slave mode.
-/// Does not control the chip select pin.
+/// Master by default, see [new_slave][Bus::new_slave()] for slave mode.
+/// Controls the chip select pin, if one is [set][Bus::set_cs()].
 pub struct Bus {
     spi: Spi,
+    cs: Option<gpio::Gpio>,
+    /// Chip select setup and hold time in microseconds.
+    cs_delay_us: u32,
 }
 
 impl Bus {
@@ -74,13 +95,118 @@ impl Bus {
     pub fn new(spi: Spi, config: Config) -> Self {
         spi.configure(config, Master::Master);
         spi.enable();
-        Self { spi }
+        trace!("spi: configured");
+        Self {
+            spi,
+            cs: None,
+            cs_delay_us: 0,
+        }
+    }
+
+    /// Slave SPI bus.
+    ///
+    /// The master drives the clock, so the configured speed is unused. The hardware chip select
+    /// pin (NSS) is an input: the bus only responds while the master pulls it low.
+    #[inline]
+    pub fn new_slave(spi: Spi, config: Config) -> Self {
+        spi.configure(config, Master::Slave);
+        spi.enable();
+        trace!("spi: configured as slave");
+        Self {
+            spi,
+            cs: None,
+            cs_delay_us: 0,
+        }
+    }
+
+    /// Block until the master clocked in a byte, in slave mode.
+    #[inline]
+    pub fn read_on_select(&mut self) -> u8 {
+        while !self.spi.rx_buffer_not_empty() {}
+        self.spi.read_data_reg()
+    }
+
+    /// Set the chip select pin, pulled low during each transfer.
+    ///
+    /// The pin is configured as push-pull output, and released. Use None to not control a chip
+    /// select pin, e.g. for daisy chained devices.
+    pub fn set_cs(&mut self, pin: Option<gpio::Gpio>) {
+        if let Some(pin) = pin {
+            gpio::write(pin, true);
+            gpio::configure(pin, gpio::Mode::OuputPushPull(gpio::Speed::Max10MHz));
+        }
+        self.cs = pin;
+    }
+
+    /// Set the time in microseconds to wait after pulling the chip select pin low, before and
+    /// after releasing it. Zero by default.
+    #[inline]
+    pub fn set_cs_delay(&mut self, micros: u32) {
+        self.cs_delay_us = micros;
+    }
+
+    /// Pull the chip select pin low, and wait the setup time.
+    #[inline]
+    fn select(&mut self) {
+        if let Some(pin) = self.cs {
+            gpio::write(pin, false);
+            micros(self.cs_delay_us);
+        }
+    }
+
+    /// Release the chip select pin, after the transfer completes, with the hold time before and
+    /// after.
+    #[inline]
+    fn deselect(&mut self) {
+        if let Some(pin) = self.cs {
+            while self.spi.busy() {}
+            micros(self.cs_delay_us);
+            gpio::write(pin, true);
+            micros(self.cs_delay_us);
+        }
+    }
+
+    /// Start writing `data` using DMA, without blocking.
+    ///
+    /// Pulls the chip select pin low, and returns the DMA channel which can be polled for
+    /// [completion][dma::Channel::is_complete()]. Call [finish_dma][Bus::finish_dma()] before
+    /// the next transfer. Assumes the bus is configured with [FrameSize::Bits8].
+    ///
+    /// ```
+    /// static DATA: [u8; 3] = [1, 2, 3];
+    /// let channel = bus.write_dma(&DATA);
+    /// // Do other work, or poll for completion.
+    /// if channel.is_complete() {
+    ///     bus.finish_dma();
+    /// }
+    /// ```
+    pub fn write_dma(&mut self, data: &'static [u8]) -> dma::Channel {
+        self.select();
+        dma::spi_tx(self.spi, data)
+    }
+
+    /// Complete a [write_dma][Bus::write_dma()] transfer.
+    ///
+    /// Blocks until the last byte is sent, and releases the chip select pin.
+    pub fn finish_dma(&mut self) {
+        let channel = dma::spi_tx_channel(self.spi);
+        // Empty transfers leave the channel disabled.
+        if channel.is_enabled() {
+            while !channel.is_complete() {}
+            channel.disable();
+            channel.clear_interrupt_flags();
+        }
+        while !self.spi.tx_buffer_empty() {}
+        self.deselect();
+        self.spi.tx_dma_enable(false);
+        self.spi.clear_overrun();
     }
 
     /// Write multiple bytes to [Register].
     #[inline]
     pub fn write(&mut self, register: impl Register, data: &[u8]) {
-        // 1. Assumed that: spi is enabled, CSN is pulled.
+        // 1. Assumed that: spi is enabled, pull CSN.
+        self.select();
 
         // Enter interrupt free block (Critical Section).
         interrupt::free(|_cs| {
@@ -112,12 +238,14 @@ impl Bus {
 
         // ... optionally disable the SPI
         // ... release CSN.
+        self.deselect();
     }
 
     /// Read multiple bytes from [Register].
     #[inline]
     pub fn read(&mut self, register: impl Register, buffer: &mut [u8]) -> u8 {
         // 1. Optionally enable spi, pull CSN.
+        self.select();
 
         // Enter interrupt free block (Critical Section).
         let len = buffer.len();
@@ -156,6 +284,7 @@ impl Bus {
 
         // ... optionally disable the SPI
         // ... release CSN.
+        self.deselect();
         read_register
     }
 
@@ -167,7 +296,8 @@ impl Bus {
         write_data: &[u8],
         read_data: &mut [u8],
     ) -> u8 {
-        // 1. Assumed that: spi is enabled, CSN is pulled.
+        // 1. Assumed that: spi is enabled, pull CSN.
+        self.select();
 
         // Enter interrupt free block (Critical Section).
         let mut read_register = 0u8;
@@ -206,10 +336,112 @@ impl Bus {
 
         // ... optionally disable the SPI
         // ... release CSN.
+        self.deselect();
 
         read_register
     }
 
+    /// Change the clock speed, rounded down as described for [Config::speed].
+    ///
+    /// Returns the actual clock speed.
+    #[inline]
+    pub fn set_speed(&mut self, speed: u32) -> u32 {
+        let actual = self.spi.set_speed(speed);
+        trace!("spi: speed = {}", actual);
+        actual
+    }
+
+    /// Full duplex transfer, without a register byte.
+    ///
+    /// Each byte in `buffer` is written, and replaced by the byte received at the same time.
+    #[inline]
+    pub fn transfer(&mut self, buffer: &mut [u8]) {
+        self.select();
+        interrupt::free(|_cs| {
+            for byte in buffer.iter_mut() {
+                while !self.spi.tx_buffer_empty() {}
+                self.spi.write_data_reg(*byte);
+                while !self.spi.rx_buffer_not_empty() {}
+                *byte = self.spi.read_data_reg();
+            }
+            while self.spi.busy() {}
+        });
+        self.deselect();
+    }
+
+    /// Full duplex transfer, without a register byte.
+    ///
+    /// Writes `tx`, and stores every byte received at the same time in `rx`. Panics if the
+    /// slices differ in length.
+    ///
+    /// With MOSI tied to MISO, the written bytes are read back:
+    ///
+    /// ```
+    /// let tx = [0xA5, 0x00, 0xFF];
+    /// let mut rx = [0u8; 3];
+    /// bus.exchange(&tx, &mut rx);
+    /// assert_eq!(rx, tx);
+    /// ```
+    #[inline]
+    pub fn exchange(&mut self, tx: &[u8], rx: &mut [u8]) {
+        assert_eq!(tx.len(), rx.len());
+        self.select();
+        interrupt::free(|_cs| {
+            for (&out, byte) in tx.iter().zip(rx.iter_mut()) {
+                while !self.spi.tx_buffer_empty() {}
+                self.spi.write_data_reg(out);
+                while !self.spi.rx_buffer_not_empty() {}
+                *byte = self.spi.read_data_reg();
+            }
+            while self.spi.busy() {}
+        });
+        self.deselect();
+    }
+
+    /// Write 16-bit frames, without a register byte.
+    ///
+    /// Requires [FrameSize::Bits16].
+    ///
+    /// ```
+    /// let mut dac = spi::Config {
+    ///     frame: spi::FrameSize::Bits16,
+    ///     ..Default::default()
+    /// }.make(spi::Spi::Spi2);
+    ///
+    /// dac.write16(&[0x3000 | 2048]);
+    /// ```
+    #[inline]
+    pub fn write16(&mut self, data: &[u16]) {
+        self.select();
+        for &word in data {
+            let _ = self.exchange16(word);
+        }
+        self.deselect();
+    }
+
+    /// Read 16-bit frames, without a register byte.
+    ///
+    /// Writes zeros. Requires [FrameSize::Bits16].
+    #[inline]
+    pub fn read16(&mut self, buffer: &mut [u16]) {
+        self.select();
+        for word in buffer.iter_mut() {
+            *word = self.exchange16(0);
+        }
+        self.deselect();
+    }
+
+    /// Write a 16-bit frame, and return the frame received in the meantime.
+    #[inline]
+    fn exchange16(&mut self, word: u16) -> u16 {
+        while !self.spi.tx_buffer_empty() {}
+        self.spi.write_data_reg16(word);
+        while !self.spi.rx_buffer_not_empty() {}
+        let received = self.spi.read_data_reg16();
+        while self.spi.busy() {}
+        received
+    }
+
     /// Write bytes without specifying the register.
     #[inline]
     pub fn write_direct(&mut self, data: &[u8]) {
@@ -249,6 +481,9 @@ impl Bus {
     }
 
     /// Write to register, and verify write by reading from register.
+    ///
+    /// Waits `wait_micros` around each transfer. The chip select timing is set by
+    /// [set_cs_delay][Bus::set_cs_delay()].
     #[inline]
     pub fn write_and_check(
         &mut self,
@@ -256,29 +491,12 @@ impl Bus {
         read_register: impl Register + Copy,
         expected: u8,
         wait_micros: u32,
-        csn: Option<gpio::Gpio>,
     ) -> Result<(), Error> {
-        micros(wait_micros);
-        if let Some(pin) = csn {
-            write_csn_and_wait(pin, false, wait_micros);
-        }
         micros(wait_micros);
         self.write_single(write_register, expected);
         micros(wait_micros);
-        if let Some(pin) = csn {
-            write_csn_and_wait(pin, true, wait_micros);
-        }
-        micros(wait_micros);
-        if let Some(pin) = csn {
-            write_csn_and_wait(pin, false, wait_micros);
-        }
-        micros(wait_micros);
         let found = self.read_single(read_register);
         micros(wait_micros);
-        if let Some(pin) = csn {
-            write_csn_and_wait(pin, true, wait_micros);
-        }
-        micros(wait_micros);
         if found == expected {
             Ok(())
         } else {
@@ -303,9 +521,3 @@ impl Register for DebugRegister {
         self.0
     }
 }
-
-fn write_csn_and_wait(pin: gpio::Gpio, value: bool, wait_micros: u32) {
-    gpio::write(pin, value);
-    micros(wait_micros);
-    while gpio::read(pin) != value {}
-}
@@ -30,6 +30,13 @@ pub enum ByteOrder {
     LsbFirst,
 }
 
+/// SPI data frame size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameSize {
+    Bits8,
+    Bits16,
+}
+
 /// SPI peripheral.
 #[derive(Copy, Clone, Debug)]
 pub enum Spi {
@@ -87,6 +94,11 @@ impl Spi {
                 }
             }
 
+            // Chip select input in slave mode.
+            if let Master::Slave = mode {
+                gpio::configure(self.nss_pin(), gpio::Mode::FloatingInput);
+            }
+
             // Enable the SPI peripheral.
             let dp = DevicePeripherals::steal();
             match self {
@@ -102,23 +114,27 @@ impl Spi {
                 // Clock polarity.
                 w.cpol().bit((config.mode as u8 >> 1) > 0);
                 w.cpha().bit((config.mode as u8 & 1) > 0);
-                // 8 bit data frame.
-                w.dff().eight_bit();
+                // Data frame size.
+                match config.frame {
+                    FrameSize::Bits8 => w.dff().eight_bit(),
+                    FrameSize::Bits16 => w.dff().sixteen_bit(),
+                };
                 // ByteOrder.
                 match config.byteorder {
                     ByteOrder::MsbFirst => w.lsbfirst().msbfirst(),
                     ByteOrder::LsbFirst => w.lsbfirst().lsbfirst(),
                 };
-                // Software slave management.
-                w.ssm().enabled();
                 // Master/Slave configuration.
                 match mode {
                     Master::Master => {
+                        // Software slave management.
+                        w.ssm().enabled();
                         w.ssi().set_bit();
                         w.mstr().master()
                     }
                     Master::Slave => {
-                        w.ssi().clear_bit();
+                        // Hardware slave management: selected by the NSS pin.
+                        w.ssm().disabled();
                         w.mstr().slave()
                     }
                 }
@@ -126,6 +142,16 @@ impl Spi {
         }
     }
 
+    /// Hardware chip select (NSS) pin.
+    #[inline]
+    pub fn nss_pin(&self) -> gpio::Gpio {
+        match self {
+            Self::Spi1(Port::A) => gpio::PA4,
+            Self::Spi1(Port::B) => gpio::PA15,
+            Self::Spi2 => gpio::PB12,
+        }
+    }
+
     #[inline]
     pub fn enable(&self) {
         unsafe {
@@ -140,11 +166,32 @@ impl Spi {
         }
     }
 
+    /// Change the clock speed, disabling the peripheral during the update.
+    ///
+    /// Returns the actual clock speed.
+    #[inline]
+    pub fn set_speed(&self, speed: u32) -> u32 {
+        while self.busy() {}
+        self.disable();
+        unsafe {
+            (*self.ptr())
+                .cr1
+                .modify(|_, w| w.br().bits(self.baudrate_register(speed)));
+        }
+        self.enable();
+        self.clk_speed()
+    }
+
     #[inline]
     pub fn clk_speed(&self) -> u32 {
         let shift = unsafe { (*self.ptr()).cr1.read().br().bits() };
-        let div = 1 << shift;
-        clock::SPEED / div
+        let clk_speed = unsafe {
+            match self {
+                Self::Spi1(_) => clock::apb2_speed(),
+                Self::Spi2 => clock::apb1_speed(),
+            }
+        };
+        clk_speed >> (shift + 1)
     }
 
     #[inline]
@@ -159,6 +206,18 @@ impl Spi {
         unsafe { (*self.ptr()).dr.read().bits() as u8 }
     }
 
+    #[inline]
+    pub fn write_data_reg16(&self, word: u16) {
+        unsafe {
+            (*self.ptr()).dr.write(|w| w.dr().bits(word));
+        }
+    }
+
+    #[inline]
+    pub fn read_data_reg16(&self) -> u16 {
+        unsafe { (*self.ptr()).dr.read().dr().bits() }
+    }
+
     #[inline]
     pub fn rx_buffer_not_empty(&self) -> bool {
         unsafe { (*self.ptr()).sr.read().rxne().bit_is_set() }
@@ -174,6 +233,30 @@ impl Spi {
         unsafe { (*self.ptr()).sr.read().bsy().bit_is_set() }
     }
 
+    /// Enable or disable the DMA request on an empty transmit buffer.
+    #[inline]
+    pub fn tx_dma_enable(&self, enable: bool) {
+        unsafe {
+            (*self.ptr()).cr2.modify(|_, w| w.txdmaen().bit(enable));
+        }
+    }
+
+    /// Address of the data register, for use as DMA peripheral address.
+    #[inline]
+    pub fn data_reg_address(&self) -> u32 {
+        unsafe { &(*self.ptr()).dr as *const _ as u32 }
+    }
+
+    /// Clear the overrun flag, and the received data.
+    #[inline]
+    pub fn clear_overrun(&self) {
+        unsafe {
+            // Cleared by reading the data register, followed by the status register.
+            let _ = (*self.ptr()).dr.read();
+            let _ = (*self.ptr()).sr.read();
+        }
+    }
+
     unsafe fn baudrate_register(&self, speed: u32) -> u8 {
         let clk_speed = match self {
             Self::Spi1(_) => {
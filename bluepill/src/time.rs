@@ -0,0 +1,120 @@
+//! Global millisecond time base.
+//!
+//! Example usage:
+//!
+//! ```
+//! let cp = cortex_m::Peripherals::take().unwrap();
+//! time::init(cp.SYST);
+//!
+//! let start = time::now();
+//! while start.elapsed() < Duration::from_millis(100) {}
+//!
+//! #[exception]
+//! fn SysTick() {
+//!     time::tick();
+//! }
+//! ```
+//!
+//! Alternatively, a general-purpose timer can drive the time base, leaving SysTick free:
+//!
+//! ```
+//! time::tim_clock(timer::TIM4);
+//!
+//! #[interrupt]
+//! fn TIM4() {
+//!     time::tim_interrupt(timer::TIM4);
+//! }
+//! ```
+//!
+//! The counter wraps after ~49 days, which is handled by [Instant].
+
+use crate::clock;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+use crate::timer::Timer;
+use cortex_m::peripheral::{syst::SystClkSource, NVIC, SYST};
+
+/// Milliseconds since [init].
+static MILLIS: AtomicU32 = AtomicU32::new(0);
+
+/// Point in time, with millisecond resolution.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Instant(u32);
+
+impl Instant {
+    /// Milliseconds since [init].
+    #[inline]
+    pub fn millis(self) -> u32 {
+        self.0
+    }
+
+    /// Time passed since `earlier`.
+    #[inline]
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        Duration::from_millis(self.0.wrapping_sub(earlier.0) as u64)
+    }
+
+    /// Time passed since this instant.
+    #[inline]
+    pub fn elapsed(self) -> Duration {
+        now().duration_since(self)
+    }
+}
+
+/// Run the SysTick timer at a one millisecond interrupt rate.
+///
+/// The rate is derived from the [active core clock][clock::hclk_hz()], so call this after
+/// setting up the system clock. The SysTick exception handler must call [tick].
+pub fn init(mut syst: SYST) {
+    syst.set_clock_source(SystClkSource::Core);
+    syst.set_reload(clock::hclk_hz() / 1_000 - 1);
+    syst.clear_current();
+    syst.enable_counter();
+    syst.enable_interrupt();
+}
+
+/// Run a timer at a one millisecond update interrupt rate, as alternative to [init].
+///
+/// The timer interrupt handler must call [tim_interrupt]. The rate is derived from the
+/// [active timer clock][Timer::clock_speed()], so call this after setting up the system clock.
+pub fn tim_clock(mut timer: Timer) {
+    let (psc, arr, _) = timer.frequency_config(1_000);
+    timer.enable_rcc();
+    timer.disable();
+    timer.write_psc(psc);
+    timer.write_arr(arr);
+    // Load the prescaler.
+    timer.update_on_overflow_only();
+    timer.generate_update();
+    timer.clear_update_interrupt_flag();
+    timer.update_interrupt_enable();
+    unsafe { NVIC::unmask(timer.update_interrupt()) };
+    timer.enable();
+}
+
+/// Interrupt handler helper for [tim_clock]: clears the update flag, and calls [tick].
+#[inline]
+pub fn tim_interrupt(timer: Timer) {
+    timer.clear_update_interrupt_flag();
+    tick();
+}
+
+/// Advance the global time by one millisecond.
+///
+/// Call this from the interrupt driving the time base.
+#[inline]
+pub fn tick() {
+    MILLIS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Current time.
+#[inline]
+pub fn now() -> Instant {
+    Instant(MILLIS.load(Ordering::Relaxed))
+}
+
+/// Milliseconds since [init] or [tim_clock].
+#[inline]
+pub fn millis() -> u32 {
+    now().millis()
+}
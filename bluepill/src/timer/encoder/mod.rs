@@ -7,6 +7,17 @@ pub use channel::{Channel, Polarity};
 pub struct Encoder {
     timer: timer::Timer,
     channels: [Channel; 2],
+    /// Counter value at the last accumulation.
+    last: u16,
+    /// Count extended to 32 bits.
+    accumulated: i32,
+}
+
+/// Encoder counting direction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -35,6 +46,8 @@ impl Encoder {
                 Channel::new(timer, timer::Channel::C1),
                 Channel::new(timer, timer::Channel::C2),
             ],
+            last: 0,
+            accumulated: 0,
         }
     }
 
@@ -53,6 +66,58 @@ impl Encoder {
         self.timer.read_counter_value()
     }
 
+    /// Counter value extended to 32 bits.
+    ///
+    /// Wraparound is detected by comparing with the previous call, so this must be called at
+    /// least once per half turn of the counter (`(arr + 1) / 2` counts).
+    #[inline]
+    pub fn read_accumulated(&mut self) -> i32 {
+        let delta = self.step();
+        self.accumulated = self.accumulated.wrapping_add(delta);
+        self.accumulated
+    }
+
+    /// Direction of the last counted edge.
+    #[inline]
+    pub fn direction(&self) -> Direction {
+        if self.timer.read_direction_down() {
+            Direction::Down
+        } else {
+            Direction::Up
+        }
+    }
+
+    /// Signed counter change since the last call, or the last call to
+    /// [read_accumulated][Encoder::read_accumulated()].
+    ///
+    /// Called at a fixed interval, this is the velocity in counts per interval.
+    #[inline]
+    pub fn delta(&mut self) -> i16 {
+        let delta = self.step();
+        self.accumulated = self.accumulated.wrapping_add(delta);
+        delta.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    }
+
+    /// Set the accumulated count to zero.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.last = self.read_counter_value();
+        self.accumulated = 0;
+    }
+
+    /// Signed counter change since the last accumulation.
+    #[inline]
+    fn step(&mut self) -> i32 {
+        let range = self.timer.read_arr() as i32 + 1;
+        let counter = self.read_counter_value();
+        let mut delta = (counter as i32 - self.last as i32).rem_euclid(range);
+        if delta > range / 2 {
+            delta -= range;
+        }
+        self.last = counter;
+        delta
+    }
+
     #[inline]
     pub fn channels<'a>(&'a mut self) -> &'a mut [Channel; 2] {
         &mut self.channels
@@ -0,0 +1,69 @@
+use super::super::timer;
+use crate::gpio;
+
+#[derive(Clone, Debug)]
+pub struct Channel {
+    timer: timer::Timer,
+    channel: timer::Channel,
+}
+
+/// Signal edge captured.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+pub enum Edge {
+    Rising = 0,
+    Falling = 1,
+}
+
+/// Number of edges per capture.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+pub enum Prescaler {
+    Div1 = 0,
+    Div2 = 1,
+    Div4 = 2,
+    Div8 = 3,
+}
+
+impl Channel {
+    #[inline]
+    pub fn new(timer: timer::Timer, channel: timer::Channel) -> Self {
+        Self { timer, channel }
+    }
+
+    /// Configure the channel, and enable capture.
+    ///
+    /// The filter (0-15) sets the number of samples for which the input must be stable.
+    #[inline]
+    pub fn configure(
+        &mut self,
+        edge: Edge,
+        filter: u8,
+        prescaler: Prescaler,
+        gpio_mode: gpio::InputMode,
+    ) {
+        self.timer
+            .input_capture_mode(self.channel, filter, prescaler as u8);
+        self.timer.polarity(self.channel, edge as u8 > 0);
+        gpio::configure(self.timer.gpio(self.channel), gpio_mode.into());
+        self.timer.capture_enable(self.channel);
+    }
+
+    /// Returns whether a new value was captured.
+    #[inline]
+    pub fn is_captured(&self) -> bool {
+        self.timer.read_capture_flag(self.channel)
+    }
+
+    /// Returns the last captured counter value, and whether a capture was missed.
+    #[inline]
+    pub fn read_capture(&mut self) -> (u16, bool) {
+        let overcapture = self.timer.take_overcapture_flag(self.channel);
+        (self.timer.read_ccr(self.channel), overcapture)
+    }
+
+    #[inline]
+    pub fn gpio(&self) -> gpio::Gpio {
+        self.timer.gpio(self.channel)
+    }
+}
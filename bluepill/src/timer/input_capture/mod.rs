@@ -0,0 +1,81 @@
+//! Input capture: store the counter value on an edge of the input signal.
+//!
+//! Example usage:
+//!
+//! ```
+//! let mut capture = input_capture::Config { psc: 71, arr: u16::MAX }.make(timer::TIM2);
+//! capture.channels()[0].configure(Edge::Rising, 0, Prescaler::Div1, gpio::InputMode::FloatingInput);
+//! capture.enable();
+//!
+//! // Period in microseconds, at 1MHz counter clock.
+//! let (t0, _) = capture.channels()[0].read_capture();
+//! while !capture.channels()[0].is_captured() {}
+//! let (t1, _) = capture.channels()[0].read_capture();
+//! let period = t1.wrapping_sub(t0);
+//! ```
+
+mod channel;
+
+use super::timer;
+pub use channel::{Channel, Edge, Prescaler};
+
+pub struct InputCapture {
+    timer: timer::Timer,
+    channels: [Channel; 4],
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    pub psc: u16,
+    pub arr: u16,
+}
+
+impl Config {
+    #[inline]
+    pub fn make(self, timer: timer::Timer) -> InputCapture {
+        InputCapture::new(timer, self)
+    }
+}
+
+impl InputCapture {
+    #[inline]
+    pub fn new(mut timer: timer::Timer, config: Config) -> Self {
+        timer.enable_rcc();
+        timer.write_arr(config.arr);
+        timer.write_psc(config.psc);
+        Self {
+            timer,
+            channels: [
+                Channel::new(timer, timer::Channel::C1),
+                Channel::new(timer, timer::Channel::C2),
+                Channel::new(timer, timer::Channel::C3),
+                Channel::new(timer, timer::Channel::C4),
+            ],
+        }
+    }
+
+    #[inline]
+    pub fn enable(&mut self) {
+        self.timer.enable();
+    }
+
+    #[inline]
+    pub fn disable(&mut self) {
+        self.timer.disable();
+    }
+
+    #[inline]
+    pub fn read_counter_value(&self) -> u16 {
+        self.timer.read_counter_value()
+    }
+
+    #[inline]
+    pub fn channels(&mut self) -> &mut [Channel; 4] {
+        &mut self.channels
+    }
+
+    #[inline]
+    pub fn into_channels(self) -> [Channel; 4] {
+        self.channels
+    }
+}
@@ -1,8 +1,13 @@
 pub mod encoder;
+pub mod input_capture;
 pub mod pwm;
+mod monotonic;
+mod periodic;
 mod timer;
 
-pub use timer::{Channel, OutputCompareMode, Timer};
+pub use monotonic::Monotonic;
+pub use periodic::{Periodic, Poll};
+pub use timer::{Channel, OutputCompareMode, Remap, Timer};
 
 pub const TIM1: timer::Timer = timer::Timer::Tim1;
 pub const TIM2: timer::Timer = timer::Timer::Tim2;
@@ -0,0 +1,82 @@
+use super::Timer;
+use core::sync::atomic::{AtomicU16, Ordering};
+
+/// Number of counter overflows per timer, the upper half of the microsecond count.
+static OVERFLOWS: [AtomicU16; 4] = [
+    AtomicU16::new(0),
+    AtomicU16::new(0),
+    AtomicU16::new(0),
+    AtomicU16::new(0),
+];
+
+/// Free running microsecond counter.
+///
+/// The counter ticks at 1MHz and wraps after 16 bits. The overflow interrupt extends it to 32
+/// bits, so its [NVIC line][Timer::update_interrupt()] must be unmasked, with the interrupt
+/// routine calling [on_interrupt][Monotonic::on_interrupt()]:
+///
+/// ```
+/// let clock = timer::Monotonic::new(timer::TIM2);
+/// unsafe { NVIC::unmask(timer::TIM2.update_interrupt()) };
+///
+/// let start = clock.now_us();
+/// // ...
+/// let elapsed = clock.now_us().wrapping_sub(start);
+///
+/// #[interrupt]
+/// fn TIM2() {
+///     timer::Monotonic::on_interrupt(timer::TIM2);
+/// }
+/// ```
+///
+/// The count wraps after about 71 minutes. Ticks are only exactly one microsecond if the timer
+/// clock is a multiple of 1MHz.
+#[derive(Copy, Clone, Debug)]
+pub struct Monotonic {
+    timer: Timer,
+}
+
+impl Monotonic {
+    /// Start the timer counting at 1MHz.
+    pub fn new(mut timer: Timer) -> Self {
+        let psc = (timer.clock_speed() / 1_000_000).clamp(1, 1 << 16) - 1;
+        timer.enable_rcc();
+        timer.disable();
+        timer.update_on_overflow_only();
+        timer.write_psc(psc as u16);
+        timer.write_arr(u16::MAX);
+        timer.generate_update();
+        timer.clear_update_interrupt_flag();
+        OVERFLOWS[timer as usize].store(0, Ordering::Relaxed);
+        timer.update_interrupt_enable();
+        timer.enable();
+        Self { timer }
+    }
+
+    /// Microseconds since [new][Monotonic::new()].
+    pub fn now_us(&self) -> u32 {
+        let overflows = &OVERFLOWS[self.timer as usize];
+        loop {
+            let high = overflows.load(Ordering::Acquire);
+            let pending = self.timer.read_update_interrupt_flag();
+            let low = self.timer.read_counter_value();
+            // Retry if the counter overflowed, or the interrupt ran, in between.
+            if pending == self.timer.read_update_interrupt_flag()
+                && high == overflows.load(Ordering::Acquire)
+            {
+                // An overflow that is not yet handled by the interrupt routine.
+                let high = high.wrapping_add(pending as u16);
+                return ((high as u32) << 16) | low as u32;
+            }
+        }
+    }
+
+    /// Count a counter overflow, call from the update interrupt of the timer.
+    #[inline]
+    pub fn on_interrupt(timer: Timer) {
+        if timer.read_update_interrupt_flag() {
+            timer.clear_update_interrupt_flag();
+            OVERFLOWS[timer as usize].fetch_add(1, Ordering::Release);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use super::Timer;
+
+/// Result of [wait][Periodic::wait()].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Poll {
+    /// The period elapsed since the last call.
+    Ready,
+    NotReady,
+}
+
+/// Periodic timer, for running code at a fixed rate.
+///
+/// Example usage:
+///
+/// ```
+/// let mut ticker = timer::Periodic::new(timer::TIM3, 1_000);
+/// ticker.start();
+///
+/// loop {
+///     if ticker.wait() == timer::Poll::Ready {
+///         // Runs at 1kHz.
+///     }
+/// }
+/// ```
+///
+/// The update interrupt is enabled, so the timer can also drive an interrupt routine by
+/// unmasking its [NVIC line][Timer::update_interrupt()]:
+///
+/// ```
+/// unsafe { NVIC::unmask(timer::TIM3.update_interrupt()) };
+/// ```
+///
+/// The interrupt routine must then clear the flag, by calling [wait][Periodic::wait()].
+#[derive(Clone, Debug)]
+pub struct Periodic {
+    timer: Timer,
+    hz: u32,
+}
+
+impl Periodic {
+    /// Configure the timer to update at `freq_hz` Hertz.
+    ///
+    /// The timer is not started, see [start][Periodic::start()].
+    pub fn new(mut timer: Timer, freq_hz: u32) -> Self {
+        let (psc, arr, hz) = timer.frequency_config(freq_hz);
+        timer.enable_rcc();
+        timer.disable();
+        timer.update_on_overflow_only();
+        timer.write_psc(psc);
+        timer.write_arr(arr);
+        timer.update_interrupt_enable();
+        Self { timer, hz }
+    }
+
+    /// Achieved frequency in Hertz.
+    #[inline]
+    pub fn frequency(&self) -> u32 {
+        self.hz
+    }
+
+    /// Restart the period from zero, and start the timer.
+    #[inline]
+    pub fn start(&mut self) {
+        self.timer.generate_update();
+        self.timer.clear_update_interrupt_flag();
+        self.timer.enable();
+    }
+
+    /// Stop the timer.
+    #[inline]
+    pub fn cancel(&mut self) {
+        self.timer.disable();
+        self.timer.clear_update_interrupt_flag();
+    }
+
+    /// Returns [Poll::Ready] once per period, clearing the update flag.
+    #[inline]
+    pub fn wait(&mut self) -> Poll {
+        if self.timer.read_update_interrupt_flag() {
+            self.timer.clear_update_interrupt_flag();
+            Poll::Ready
+        } else {
+            Poll::NotReady
+        }
+    }
+}
@@ -49,6 +49,22 @@ impl Channel {
         self.timer.output_enable(self.channel);
     }
 
+    /// Disable the output of this channel, leaving the timer and other channels running.
+    #[inline]
+    pub fn output_disable(&mut self) {
+        self.timer.output_disable(self.channel);
+    }
+
+    /// Enable or disable the output of this channel.
+    #[inline]
+    pub fn set_enabled(&mut self, enable: bool) {
+        if enable {
+            self.timer.output_enable(self.channel);
+        } else {
+            self.timer.output_disable(self.channel);
+        }
+    }
+
     #[inline]
     pub fn write_ccr(&mut self, ccr: u16) {
         self.timer.write_ccr(self.channel, ccr);
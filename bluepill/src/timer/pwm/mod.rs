@@ -19,6 +19,27 @@ impl Config {
     pub fn make(self, timer: timer::Timer) -> Pwm {
         Pwm::new(timer, self)
     }
+
+    /// PWM configuration for a target frequency, with the largest possible [resolution].
+    ///
+    /// Returns the configuration and the achieved frequency in Hertz.
+    ///
+    /// ```
+    /// let (config, hz) = pwm::Config::from_frequency(timer::TIM1, 2_000);
+    /// let pwm = config.make(timer::TIM1);
+    /// ```
+    ///
+    /// [resolution]: Config::resolution()
+    pub fn from_frequency(timer: timer::Timer, hz: u32) -> (Self, u32) {
+        let (psc, arr, hz) = timer.frequency_config(hz);
+        (Self { psc, arr }, hz)
+    }
+
+    /// Number of PWM duty cycle steps.
+    #[inline]
+    pub fn resolution(&self) -> u32 {
+        self.arr as u32 + 1
+    }
 }
 
 impl Pwm {
@@ -1,7 +1,7 @@
-use crate::gpio;
+use crate::{clock, gpio};
 use stm32f1xx_hal::pac::{
     tim1::RegisterBlock as RegisterBlock1, tim2::RegisterBlock as RegisterBlock2,
-    Peripherals as DevicePeripherals, TIM1, TIM2, TIM3, TIM4,
+    Interrupt, Peripherals as DevicePeripherals, TIM1, TIM2, TIM3, TIM4,
 };
 
 #[repr(u8)]
@@ -17,6 +17,25 @@ pub enum SlaveMode {
     ExternalClockMode = 7,
 }
 
+/// Alternate pin mapping of the timer channels.
+///
+/// Valid combinations, on the 48-pin blue pill package:
+///
+/// | Timer | Remap     | CH1  | CH2  | CH3   | CH4   | CH1N | CH2N  | CH3N  |
+/// |-------|-----------|------|------|-------|-------|------|-------|-------|
+/// | TIM1  | `None`    | PA8  | PA9  | PA10  | PA11  | PB13 | PB14  | PB15  |
+/// | TIM1  | `Partial` | PA8  | PA9  | PA10  | PA11  | PA7  | PB0   | PB1   |
+/// | TIM3  | `None`    | PA6  | PA7  | PB0   | PB1   |      |       |       |
+/// | TIM3  | `Partial` | PB4  | PB5  | PB0   | PB1   |      |       |       |
+///
+/// The full remaps, TIM1 to PE8..PE15 and TIM3 to PC6..PC9, use pins that are not available on
+/// this package. TIM2 and TIM4 are not remapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Remap {
+    None,
+    Partial,
+}
+
 enum TimerPtr {
     Tim1(*const RegisterBlock1),
     Tim234(*const RegisterBlock2),
@@ -76,6 +95,31 @@ impl Timer {
         }
     }
 
+    /// Counter clock speed before the prescaler, in Hertz.
+    #[inline]
+    pub fn clock_speed(&self) -> u32 {
+        unsafe {
+            match self {
+                Timer::Tim1 => clock::apb2_timer_speed(),
+                _ => clock::apb1_timer_speed(),
+            }
+        }
+    }
+
+    /// Prescaler and auto reload values for a target update frequency.
+    ///
+    /// Uses the smallest prescaler for which the reload value fits in 16 bits, and returns
+    /// `(psc, arr, achieved frequency in Hertz)`.
+    pub(crate) fn frequency_config(&self, hz: u32) -> (u16, u16, u32) {
+        let clk = self.clock_speed();
+        let ticks = (clk / hz.max(1)).max(2);
+        let psc = ((ticks - 1) >> 16).min(u16::MAX as u32);
+        let div = psc + 1;
+        // Rounded to the nearest period.
+        let arr = ((ticks + div / 2) / div).clamp(2, 1 << 16) - 1;
+        (psc as u16, arr as u16, clk / div / (arr + 1))
+    }
+
     #[inline]
     pub fn write_arr(&mut self, arr: u16) {
         unsafe {
@@ -146,6 +190,45 @@ impl Timer {
         }
     }
 
+    /// Reset the counter and load the prescaler, by generating an update event.
+    ///
+    /// Also sets the update flag, unless [update_on_overflow_only] was set.
+    ///
+    /// [update_on_overflow_only]: Timer::update_on_overflow_only()
+    #[inline]
+    pub fn generate_update(&mut self) {
+        unsafe {
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => (*ptr).egr.write(|w| w.ug().update()),
+                TimerPtr::Tim234(ptr) => (*ptr).egr.write(|w| w.ug().update()),
+            }
+        }
+    }
+
+    /// Only counter overflow sets the update flag, not [generate_update].
+    ///
+    /// [generate_update]: Timer::generate_update()
+    #[inline]
+    pub fn update_on_overflow_only(&mut self) {
+        unsafe {
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => (*ptr).cr1.modify(|_, w| w.urs().counter_only()),
+                TimerPtr::Tim234(ptr) => (*ptr).cr1.modify(|_, w| w.urs().counter_only()),
+            }
+        }
+    }
+
+    /// Returns whether the counter counts down.
+    #[inline]
+    pub fn read_direction_down(&self) -> bool {
+        unsafe {
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => (*ptr).cr1.read().dir().bit_is_set(),
+                TimerPtr::Tim234(ptr) => (*ptr).cr1.read().dir().bit_is_set(),
+            }
+        }
+    }
+
     #[inline]
     pub fn write_slave_mode(&mut self, mode: SlaveMode) {
         let x = mode as u32;
@@ -164,6 +247,42 @@ impl Timer {
         }
     }
 
+    /// Select the pins of the timer channels, see [Remap].
+    ///
+    /// A no-op for TIM2 and TIM4. PB4 is a JTAG pin, see [free_jtag][gpio::free_jtag()].
+    #[inline]
+    pub fn set_remap(&mut self, remap: Remap) {
+        let bits = match remap {
+            Remap::None => 0,
+            Remap::Partial => 1,
+        };
+        unsafe {
+            let dp = DevicePeripherals::steal();
+            match self {
+                Timer::Tim1 => dp.AFIO.mapr.modify(|_, w| w.tim1_remap().bits(bits)),
+                Timer::Tim3 => dp.AFIO.mapr.modify(|_, w| w.tim3_remap().bits(bits << 1)),
+                Timer::Tim2 | Timer::Tim4 => (),
+            }
+        }
+    }
+
+    /// Active pin mapping, as set by [set_remap][Timer::set_remap()].
+    #[inline]
+    pub fn remap(&self) -> Remap {
+        let mapr = unsafe { DevicePeripherals::steal().AFIO.mapr.read() };
+        let bits = match self {
+            Timer::Tim1 => mapr.tim1_remap().bits(),
+            Timer::Tim3 => mapr.tim3_remap().bits(),
+            Timer::Tim2 | Timer::Tim4 => 0,
+        };
+        if bits == 0 {
+            Remap::None
+        } else {
+            Remap::Partial
+        }
+    }
+
+    /// Pin of a channel, for the active [remap][Timer::remap()].
     #[inline]
     pub fn gpio(&self, channel: Channel) -> gpio::Gpio {
         match self {
@@ -180,6 +299,8 @@ impl Timer {
                 Channel::C4 => gpio::PA3,
             },
             Timer::Tim3 => match channel {
+                Channel::C1 if self.remap() == Remap::Partial => gpio::PB4,
+                Channel::C2 if self.remap() == Remap::Partial => gpio::PB5,
                 Channel::C1 => gpio::PA6,
                 Channel::C2 => gpio::PA7,
                 Channel::C3 => gpio::PB0,
@@ -234,6 +355,95 @@ impl Timer {
         }
     }
 
+    /// Configure channel as input capture, on its own input (TIx).
+    ///
+    /// The filter (0-15) and prescaler (0-3) are written to the CCMR register as is.
+    #[inline]
+    pub fn input_capture_mode(&self, channel: Channel, filter: u8, prescaler: u8) {
+        let shift = match channel {
+            Channel::C1 | Channel::C3 => 0,
+            Channel::C2 | Channel::C4 => 8,
+        };
+        // CCxS = 01: input, mapped on TIx.
+        let x = (((filter as u32 & 0xF) << 4) | ((prescaler as u32 & 3) << 2) | 1) << shift;
+        let mask = !(0xFF << shift);
+        unsafe {
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => match channel {
+                    Channel::C1 | Channel::C2 => {
+                        let value = (*ptr).ccmr1_output().read().bits() & mask;
+                        (*ptr).ccmr1_output().modify(|_, w| w.bits(value | x));
+                    }
+                    Channel::C3 | Channel::C4 => {
+                        let value = (*ptr).ccmr2_output().read().bits() & mask;
+                        (*ptr).ccmr2_output().modify(|_, w| w.bits(value | x));
+                    }
+                },
+                TimerPtr::Tim234(ptr) => match channel {
+                    Channel::C1 | Channel::C2 => {
+                        let value = (*ptr).ccmr1_output().read().bits() & mask;
+                        (*ptr).ccmr1_output().modify(|_, w| w.bits(value | x));
+                    }
+                    Channel::C3 | Channel::C4 => {
+                        let value = (*ptr).ccmr2_output().read().bits() & mask;
+                        (*ptr).ccmr2_output().modify(|_, w| w.bits(value | x));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Enable capture on an input channel.
+    #[inline]
+    pub fn capture_enable(&mut self, channel: Channel) {
+        unsafe {
+            let x = 1 << (4 * channel as u8);
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => {
+                    let value = (*ptr).ccer.read().bits();
+                    (*ptr).ccer.modify(|_, w| w.bits(x | value));
+                }
+                TimerPtr::Tim234(ptr) => {
+                    let value = (*ptr).ccer.read().bits();
+                    (*ptr).ccer.modify(|_, w| w.bits(x | value));
+                }
+            }
+        }
+    }
+
+    /// Returns whether a capture occurred while the previous one was not read, and clears the flag.
+    #[inline]
+    pub fn take_overcapture_flag(&self, channel: Channel) -> bool {
+        let x = 1 << (9 + channel as u8);
+        unsafe {
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => {
+                    let set = (*ptr).sr.read().bits() & x > 0;
+                    // Flags are cleared by writing zero.
+                    (*ptr).sr.write(|w| w.bits(0xFFFF & !x));
+                    set
+                }
+                TimerPtr::Tim234(ptr) => {
+                    let set = (*ptr).sr.read().bits() & x > 0;
+                    (*ptr).sr.write(|w| w.bits(0xFFFF & !x));
+                    set
+                }
+            }
+        }
+    }
+
+    /// Returns whether a capture occurred on the channel, since the last read of its CCR register.
+    #[inline]
+    pub fn read_capture_flag(&self, channel: Channel) -> bool {
+        let x = 1 << (1 + channel as u8);
+        unsafe {
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => (*ptr).sr.read().bits() & x > 0,
+                TimerPtr::Tim234(ptr) => (*ptr).sr.read().bits() & x > 0,
+            }
+        }
+    }
+
     #[inline]
     pub fn output_enable(&mut self, channel: Channel) {
         unsafe {
@@ -253,6 +463,66 @@ impl Timer {
         }
     }
 
+    /// Disable the output of a channel.
+    ///
+    /// For TIM1 the main output is also disabled, once no channel output is enabled.
+    #[inline]
+    pub fn output_disable(&mut self, channel: Channel) {
+        unsafe {
+            let x = 1 << (4 * channel as u8);
+            match self.ptr() {
+                TimerPtr::Tim1(ptr) => {
+                    let value = (*ptr).ccer.read().bits() & !x;
+                    (*ptr).ccer.write(|w| w.bits(value));
+                    // Enable bits of the (complementary) channel outputs.
+                    if value & 0x1555 == 0 {
+                        (*ptr).bdtr.modify(|_, w| w.moe().clear_bit());
+                    }
+                }
+                TimerPtr::Tim234(ptr) => {
+                    let value = (*ptr).ccer.read().bits();
+                    (*ptr).ccer.write(|w| w.bits(value & !x));
+                }
+            }
+        }
+    }
+
+    /// Enable the complementary output (CHxN) of a channel.
+    ///
+    /// Only TIM1 channels 1-3 have a complementary output, for other timers and channel 4 this is
+    /// a no-op.
+    #[inline]
+    pub fn enable_complementary_output(&mut self, channel: Channel) {
+        let ptr = self.ptr();
+        if let (TimerPtr::Tim1(ptr), Channel::C1 | Channel::C2 | Channel::C3) = (ptr, channel) {
+            unsafe {
+                let x = 1 << (2 + 4 * channel as u8);
+                let value = (*ptr).ccer.read().bits();
+                (*ptr).ccer.modify(|_, w| w.bits(x | value));
+            }
+        }
+    }
+
+    /// Set the dead-time inserted between complementary outputs, rounded up.
+    ///
+    /// Clamped to the longest dead-time: 1008 timer clock cycles, 14us at 72MHz.
+    /// Only TIM1 has a dead-time generator, for other timers this is a no-op.
+    #[inline]
+    pub fn set_dead_time(&mut self, ns: u16) {
+        if let TimerPtr::Tim1(ptr) = self.ptr() {
+            let ticks = (ns as u32 * (self.clock_speed() / 1_000_000)).div_ceil(1_000);
+            let dtg = match ticks {
+                0..=127 => ticks,
+                128..=254 => 0b1000_0000 | (ticks.div_ceil(2) - 64),
+                255..=504 => 0b1100_0000 | (ticks.div_ceil(8) - 32),
+                _ => 0b1110_0000 | (ticks.div_ceil(16).min(63) - 32),
+            };
+            unsafe {
+                (*ptr).bdtr.modify(|_, w| w.dtg().bits(dtg as u8));
+            }
+        }
+    }
+
     #[inline]
     pub fn polarity(&self, channel: Channel, pol: bool) {
         unsafe {
@@ -341,4 +611,15 @@ impl Timer {
             }
         }
     }
+
+    /// Interrupt line of the update event.
+    #[inline]
+    pub fn update_interrupt(&self) -> Interrupt {
+        match self {
+            Timer::Tim1 => Interrupt::TIM1_UP,
+            Timer::Tim2 => Interrupt::TIM2,
+            Timer::Tim3 => Interrupt::TIM3,
+            Timer::Tim4 => Interrupt::TIM4,
+        }
+    }
 }
@@ -11,6 +11,7 @@
 //! let mut bus = usart::Config {
 //!     baudrate: 1_000_000,
 //!     tx_pin: OutputMode::PushPull(Speed::Max50MHz),
+//!     ..Default::default()
 //! }.make(peripheral);
 //!
 //! // Write data to bus.
@@ -19,11 +20,14 @@
 //! ```
 
 mod pac;
+mod ring_buffer;
 
-pub use pac::{Port, Usart};
+pub use pac::{Parity, Port, StopBits, Usart, WordLength};
+pub use ring_buffer::RingBuffer;
+use ring_buffer::RxBuffer;
 use gpio::{OutputMode, InputMode};
 
-use crate::gpio;
+use crate::{dma, gpio};
 
 /// Usart peripheral configuration.
 ///
@@ -34,6 +38,40 @@ pub struct Config {
     pub baudrate: u32,
     /// Set output mode of the TX pin.
     pub tx_pin: OutputMode,
+    /// Parity bit.
+    pub parity: Parity,
+    /// Word length, ignored with parity enabled, see [WordLength].
+    pub word_length: WordLength,
+    /// Number of stop bits.
+    ///
+    /// ```
+    /// let config = uart::Config {
+    ///     baudrate: 9600,
+    ///     tx_pin: OutputMode::PushPull(Speed::Max2MHz),
+    ///     stop_bits: uart::StopBits::Two,
+    ///     ..Default::default()
+    /// };
+    /// ```
+    pub stop_bits: StopBits,
+    /// Single wire half duplex mode.
+    ///
+    /// Transmits and receives on the TX pin, which is configured as open drain, so the line must
+    /// be pulled high externally. The RX pin is unused.
+    pub half_duplex: bool,
+}
+
+impl Default for Config {
+    /// 115200 baud, 8 data bits, no parity, one stop bit.
+    fn default() -> Self {
+        Self {
+            baudrate: 115_200,
+            tx_pin: OutputMode::PushPull(gpio::Speed::Max10MHz),
+            parity: Parity::None,
+            word_length: WordLength::Eight,
+            stop_bits: StopBits::One,
+            half_duplex: false,
+        }
+    }
 }
 
 impl Config {
@@ -43,21 +81,48 @@ impl Config {
     }
 }
 
+/// Uart receive errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A byte was received before the previous one was read.
+    Overrun,
+    /// No stop bit detected.
+    Framing,
+    /// Noise detected on the line.
+    Noise,
+    /// Parity check failed.
+    Parity,
+}
+
 /// Uart bus.
 ///
 /// Can be constructed using [Config][Config::make()].
 pub struct Bus {
     usart: Usart,
     tx_pin: OutputMode,
+    rx_buffer: Option<&'static dyn RxBuffer>,
 }
 
 impl Bus {
     #[inline]
     pub fn new(usart: Usart, config: Config) -> Self {
-        usart.configure(config.baudrate);
+        usart.configure(
+            config.baudrate,
+            config.parity,
+            config.word_length,
+            config.stop_bits,
+            config.half_duplex,
+        );
+        trace!("uart: configured, baudrate = {}", config.baudrate);
+        let tx_pin = match config.tx_pin {
+            OutputMode::PushPull(speed) if config.half_duplex => OutputMode::OpenDrain(speed),
+            tx_pin => tx_pin,
+        };
         Self {
             usart,
-            tx_pin: config.tx_pin,
+            tx_pin,
+            rx_buffer: None,
         }
     }
 }
@@ -75,6 +140,44 @@ impl Bus {
         }
     }
 
+    /// Attach a receive buffer, and enable the receive interrupt.
+    ///
+    /// The USART interrupt must call [receive][RingBuffer::receive()] on the buffer.
+    ///
+    /// # Safety
+    ///
+    /// The bus becomes the consumer of the buffer: it must not be attached to another bus, or
+    /// be [popped][RingBuffer::pop()] elsewhere.
+    pub unsafe fn attach_rx_buffer<const N: usize>(&mut self, buffer: &'static RingBuffer<N>) {
+        self.rx_buffer = Some(buffer);
+        self.rx_interrupt_enable(true);
+        self.unmask_interrupts();
+    }
+
+    /// Read the oldest byte from the attached receive buffer.
+    ///
+    /// Returns None if the buffer is empty, or if no buffer is attached.
+    #[inline]
+    pub fn read_buffered(&mut self) -> Option<u8> {
+        // Safety: the bus is the only consumer, see [attach_rx_buffer][Bus::attach_rx_buffer()].
+        self.rx_buffer.and_then(|buffer| unsafe { buffer.pop() })
+    }
+
+    /// Returns whether received bytes were dropped on a full buffer since the last call.
+    #[inline]
+    pub fn rx_overrun(&mut self) -> bool {
+        self.rx_buffer.is_some_and(|buffer| buffer.take_overrun())
+    }
+
+    /// Read received byte, checking for receive errors.
+    ///
+    /// Returns Ok(None) if buffer is empty. On error the received byte is dropped, and the error
+    /// flag is cleared.
+    #[inline]
+    pub fn read_byte_checked(&mut self) -> Result<Option<u8>, Error> {
+        self.usart.read_checked()
+    }
+
     /// Write byte.
     ///
     /// Returns Error if buffer is not empty.
@@ -88,6 +191,27 @@ impl Bus {
         }
     }
 
+    /// Write a 9-bit word, blocks until the transmit buffer is empty.
+    ///
+    /// Requires [WordLength::Nine] without parity.
+    #[inline]
+    pub fn write_word(&mut self, word: u16) {
+        while !self.usart.tx_buffer_empty() {}
+        self.usart.write_data_reg9(word);
+    }
+
+    /// Read a received 9-bit word.
+    ///
+    /// Returns None if buffer is empty. Requires [WordLength::Nine] without parity.
+    #[inline]
+    pub fn read_word(&mut self) -> Option<u16> {
+        if self.usart.rx_buffer_not_empty() {
+            Some(self.usart.read_data_reg9())
+        } else {
+            None
+        }
+    }
+
     /// Returns TX pin of current USART peripheral.
     #[inline]
     pub fn get_tx_pin(&self) -> gpio::Gpio {
@@ -95,6 +219,8 @@ impl Bus {
     }
 
     /// Returns RX pin of current USART peripheral.
+    ///
+    /// Unused in [half duplex][Config::half_duplex] mode.
     #[inline]
     pub fn get_rx_pin(&self) -> gpio::Gpio {
        self.usart.get_rx_pin()
@@ -108,7 +234,8 @@ impl Bus {
 
     /// Enable or disable transmitter.
     ///
-    /// When disabled, the TX pin will be configured as floating input.
+    /// When disabled, the TX pin will be configured as floating input. Use
+    /// [flush][Bus::flush()] before disabling to finish the ongoing transmission.
     #[inline]
     pub fn tx_enable(&mut self, enable: bool) {
         self.usart.tx_enable(enable);
@@ -152,6 +279,34 @@ impl Bus {
         }
     }
 
+    /// Start writing `data` using DMA, without blocking.
+    ///
+    /// Returns the DMA channel, which can be polled for
+    /// [completion][dma::Channel::is_complete()]. See [dma::uart_tx].
+    #[inline]
+    pub fn write_dma(&mut self, data: &'static [u8]) -> dma::Channel {
+        dma::uart_tx(self.usart, data)
+    }
+
+    /// Change the baudrate, keeping the rest of the configuration.
+    ///
+    /// The divider is rounded down: at 115200 baud on the 36MHz APB1 (USART2, USART3) the actual
+    /// baudrate is 115384, 0.16% too fast. Call [flush][Bus::flush()] first to not corrupt an
+    /// ongoing transmission.
+    #[inline]
+    pub fn set_baudrate(&mut self, baudrate: u32) {
+        self.usart.set_baudrate(baudrate);
+        trace!("uart: baudrate = {}", baudrate);
+    }
+
+    /// Blocks until the last byte has been shifted out.
+    ///
+    /// Call before disabling the transmitter, to not truncate the last byte.
+    #[inline]
+    pub fn flush(&mut self) {
+        while !self.usart.is_transmission_complete() {}
+    }
+
     // Enable or disable interrupts.
     #[inline]
     pub fn set_intterupts_mask(&mut self, mask: bool) {
@@ -172,13 +327,29 @@ impl Bus {
         self.usart.mask_interrupts();
     }
 
+    /// Enable or disable the interrupt on a received byte (RXNE).
     #[inline]
     pub fn rx_interrupt_enable(&mut self, enable: bool) {
         self.usart.rx_interrupt_enable(enable)
     }
 
+    /// Enable or disable the interrupt on an empty transmit buffer (TXE).
     #[inline]
     pub fn tx_interrupt_enable(&mut self, enable: bool) {
-        self.usart.rx_interrupt_enable(enable)
+        self.usart.tx_interrupt_enable(enable)
+    }
+}
+
+/// Formatted output, blocking until all bytes are in the transmit buffer.
+///
+/// ```
+/// use core::fmt::Write;
+///
+/// writeln!(bus, "angle={} speed={}", sample.angle, sample.speed).unwrap();
+/// ```
+impl core::fmt::Write for Bus {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use super::Error;
 use crate::{clock, gpio};
 use cortex_m::peripheral::NVIC;
 use stm32f1xx_hal::pac::Interrupt;
@@ -15,6 +16,40 @@ pub enum Usart {
     Usart3,
 }
 
+/// Parity bit.
+///
+/// The parity bit is part of the data word, so with parity enabled the peripheral is configured
+/// for 9-bit words (`m().m9()`) to keep 8 data bits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+/// Word length, including the parity bit.
+///
+/// With parity enabled the word is always 9 bits: 8 data bits and the parity bit. Without
+/// parity, [Nine][WordLength::Nine] gives 9 data bits, e.g. for an address marker in multidrop
+/// protocols. Use [write_word][super::Bus::write_word()] and
+/// [read_word][super::Bus::read_word()] for the 9th bit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WordLength {
+    Eight,
+    Nine,
+}
+
+/// Number of stop bits.
+///
+/// Half and one and a half stop bits are intended for smartcard mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopBits {
+    Half,
+    One,
+    OneAndHalf,
+    Two,
+}
+
 /// Available GPIO ports for Usart1.
 #[derive(Copy, Clone, Debug)]
 pub enum Port {
@@ -51,13 +86,18 @@ impl Usart {
         }
     }
 
+    /// Configure the alternate function pin mapping.
+    ///
+    /// USART2 and USART3 always use their default pins: the remapped pins (PD5/PD6, PC10/PC11
+    /// and PD8/PD9) are not available on the blue pill's 48 pin package.
     pub fn configure_af_remap(&self) {
         unsafe {
             let dp = DevicePeripherals::steal();
             match self {
                 Self::Usart1(Port::A) => dp.AFIO.mapr.modify(|_, w| w.usart1_remap().clear_bit()),
                 Self::Usart1(Port::B) => dp.AFIO.mapr.modify(|_, w| w.usart1_remap().set_bit()),
-                _ => (),
+                // Not remapped after reset.
+                Self::Usart2 | Self::Usart3 => (),
             }
         }
     }
@@ -71,7 +111,14 @@ impl Usart {
     }
 
     #[inline]
-    pub fn configure(&self, baudrate: u32) {
+    pub fn configure(
+        &self,
+        baudrate: u32,
+        parity: Parity,
+        word_length: WordLength,
+        stop_bits: StopBits,
+        half_duplex: bool,
+    ) {
         unsafe {
             // Enable the peripheral.
             let dp = DevicePeripherals::steal();
@@ -83,36 +130,72 @@ impl Usart {
             gpio::enable_alternate_function_io();
             self.configure_af_remap();
 
-            // Baudrate register.
-            // let peripheral_clock = crate::clock::SPEED / 2;
-            let peripheral_clock = match self {
-                Self::Usart1(_) => {
-                    // Clock obtained from APB2.
-                    clock::apb2_speed()
-                }
-                _ => {
-                    // Clock obtained from APB1.
-                    clock::apb1_speed()
-                }
-            };
-            let divider = peripheral_clock / baudrate;
-            (*self.ptr()).brr.modify(|_, w| {
-                w.div_mantissa().bits((divider / 16) as u16);
-                w.div_fraction().bits((divider % 16) as u8)
-            });
+            self.write_baudrate(baudrate);
+
+            // Single wire half duplex: RX is internally connected to TX.
+            (*self.ptr()).cr3.modify(|_, w| w.hdsel().bit(half_duplex));
 
             (*self.ptr()).cr1.modify(|_, w| {
                 w.ue().enabled(); // Enable the USART.
-                w.m().m8(); // 8 data bits.
-                w.pce().disabled() // No parity check.
+                match parity {
+                    Parity::None => {
+                        match word_length {
+                            WordLength::Eight => w.m().m8(), // 8 data bits.
+                            WordLength::Nine => w.m().m9(), // 9 data bits.
+                        };
+                        w.pce().disabled() // No parity check.
+                    }
+                    Parity::Even => {
+                        w.m().m9(); // 8 data bits + parity.
+                        w.pce().enabled();
+                        w.ps().even()
+                    }
+                    Parity::Odd => {
+                        w.m().m9(); // 8 data bits + parity.
+                        w.pce().enabled();
+                        w.ps().odd()
+                    }
+                }
             });
 
-            (*self.ptr()).cr2.modify(|_, w| {
-                w.stop().stop1() // One stop bit.
+            (*self.ptr()).cr2.modify(|_, w| match stop_bits {
+                StopBits::Half => w.stop().stop0p5(),
+                StopBits::One => w.stop().stop1(),
+                StopBits::OneAndHalf => w.stop().stop1p5(),
+                StopBits::Two => w.stop().stop2(),
             });
         }
     }
 
+    /// Change the baudrate, disabling the USART during the update.
+    #[inline]
+    pub fn set_baudrate(&self, baudrate: u32) {
+        unsafe {
+            (*self.ptr()).cr1.modify(|_, w| w.ue().disabled());
+            self.write_baudrate(baudrate);
+            (*self.ptr()).cr1.modify(|_, w| w.ue().enabled());
+        }
+    }
+
+    /// Baudrate register.
+    unsafe fn write_baudrate(&self, baudrate: u32) {
+        let peripheral_clock = match self {
+            Self::Usart1(_) => {
+                // Clock obtained from APB2.
+                clock::apb2_speed()
+            }
+            _ => {
+                // Clock obtained from APB1.
+                clock::apb1_speed()
+            }
+        };
+        let divider = peripheral_clock / baudrate;
+        (*self.ptr()).brr.modify(|_, w| {
+            w.div_mantissa().bits((divider / 16) as u16);
+            w.div_fraction().bits((divider % 16) as u8)
+        });
+    }
+
     #[inline]
     pub fn rx_enable(&self, enable: bool) {
         unsafe {
@@ -147,6 +230,7 @@ impl Usart {
         }
     }
 
+    /// Set the RXNEIE bit (CR1 bit 5).
     #[inline]
     pub fn rx_interrupt_enable(&self, enable: bool) {
         unsafe {
@@ -154,6 +238,7 @@ impl Usart {
         }
     }
 
+    /// Set the TXEIE bit (CR1 bit 7).
     #[inline]
     pub fn tx_interrupt_enable(&self, enable: bool) {
         unsafe {
@@ -173,6 +258,50 @@ impl Usart {
         unsafe { (*self.ptr()).dr.read().bits() as u8 }
     }
 
+    /// Write a 9-bit word.
+    #[inline]
+    pub fn write_data_reg9(&self, word: u16) {
+        unsafe {
+            (*self.ptr()).dr.write(|w| w.dr().bits(word & 0x1FF));
+        }
+    }
+
+    /// Read a 9-bit word.
+    #[inline]
+    pub fn read_data_reg9(&self) -> u16 {
+        unsafe { (*self.ptr()).dr.read().dr().bits() & 0x1FF }
+    }
+
+    /// Read a received byte, checking the error flags.
+    ///
+    /// An error is cleared by reading the status register followed by the data register, which
+    /// drops the received byte.
+    #[inline]
+    pub fn read_checked(&self) -> Result<Option<u8>, Error> {
+        unsafe {
+            let sr = (*self.ptr()).sr.read();
+            let error = if sr.ore().bit_is_set() {
+                Some(Error::Overrun)
+            } else if sr.fe().bit_is_set() {
+                Some(Error::Framing)
+            } else if sr.ne().bit_is_set() {
+                Some(Error::Noise)
+            } else if sr.pe().bit_is_set() {
+                Some(Error::Parity)
+            } else {
+                None
+            };
+            match error {
+                Some(error) => {
+                    let _ = self.read_data_reg();
+                    Err(error)
+                }
+                None if sr.rxne().bit_is_set() => Ok(Some(self.read_data_reg())),
+                None => Ok(None),
+            }
+        }
+    }
+
     #[inline]
     pub fn rx_buffer_not_empty(&self) -> bool {
         unsafe { (*self.ptr()).sr.read().rxne().bit_is_set() }
@@ -183,6 +312,29 @@ impl Usart {
         unsafe { (*self.ptr()).sr.read().txe().bit_is_set() }
     }
 
+    /// Enable or disable the DMA request on an empty transmit buffer.
+    #[inline]
+    pub fn tx_dma_enable(&self, enable: bool) {
+        unsafe {
+            (*self.ptr()).cr3.modify(|_, w| w.dmat().bit(enable));
+        }
+    }
+
+    /// Address of the data register, for use as DMA peripheral address.
+    #[inline]
+    pub fn data_reg_address(&self) -> u32 {
+        unsafe { &(*self.ptr()).dr as *const _ as u32 }
+    }
+
+    /// Clear the transmission complete flag.
+    #[inline]
+    pub fn clear_transmission_complete(&self) {
+        unsafe {
+            // Writing ones leaves the other clear-by-zero flags, e.g. RXNE, untouched.
+            (*self.ptr()).sr.write(|w| w.bits(!(1 << 6)));
+        }
+    }
+
     #[inline]
     pub fn is_transmission_complete(&self) -> bool {
         unsafe { (*self.ptr()).sr.read().tc().bit_is_set() }
@@ -0,0 +1,149 @@
+use super::Usart;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Receive buffer, filled from the USART interrupt.
+///
+/// Single producer (the interrupt) and single consumer (the [Bus][super::Bus]). Both sides are
+/// `unsafe`, as nothing else enforces a single context per side. The size `N` must be a power
+/// of two.
+///
+/// Example usage:
+///
+/// ```
+/// static RX_BUFFER: uart::RingBuffer<64> = uart::RingBuffer::new();
+///
+/// // Safety: the bus is the only consumer of the buffer.
+/// unsafe { bus.attach_rx_buffer(&RX_BUFFER) };
+/// while let Some(byte) = bus.read_buffered() {
+///     // ...
+/// }
+///
+/// #[interrupt]
+/// fn USART2() {
+///     // Safety: this interrupt is the only producer of the buffer.
+///     unsafe { RX_BUFFER.receive(uart::Usart::Usart2) };
+/// }
+/// ```
+pub struct RingBuffer<const N: usize> {
+    data: UnsafeCell<[u8; N]>,
+    /// Total number of bytes written.
+    head: AtomicUsize,
+    /// Total number of bytes read.
+    tail: AtomicUsize,
+    /// Set when a byte was dropped on a full buffer.
+    overrun: AtomicBool,
+}
+
+// Safety: head and tail are each only written by one side, and a slot is only accessed by the
+// side owning it. A single context per side is required by the unsafe push and pop.
+unsafe impl<const N: usize> Sync for RingBuffer<N> {}
+
+impl<const N: usize> RingBuffer<N> {
+    /// Slots are indexed by the wrapping counters modulo `N`, which only stays consistent across
+    /// the counter wraparound for a power of two.
+    const POWER_OF_TWO: () = assert!(N.is_power_of_two(), "RingBuffer size must be a power of two");
+
+    pub const fn new() -> Self {
+        let () = Self::POWER_OF_TWO;
+        Self {
+            data: UnsafeCell::new([0; N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overrun: AtomicBool::new(false),
+        }
+    }
+
+    /// Push a byte.
+    ///
+    /// Returns false, dropping the byte and setting the overrun flag, if the buffer is full.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called from a single context, e.g. one interrupt routine, which is the only
+    /// producer of the buffer.
+    pub unsafe fn push(&self, byte: u8) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(self.tail.load(Ordering::Acquire)) >= N {
+            self.overrun.store(true, Ordering::Relaxed);
+            return false;
+        }
+        (*self.data.get())[head % N] = byte;
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Pop the oldest byte.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called from a single context, which is the only consumer of the buffer.
+    pub unsafe fn pop(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if self.head.load(Ordering::Acquire) == tail {
+            return None;
+        }
+        let byte = (*self.data.get())[tail % N];
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+
+    /// Number of bytes in the buffer.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.head
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.tail.load(Ordering::Acquire))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Move a received byte from the USART into the buffer.
+    ///
+    /// Call from the USART interrupt.
+    ///
+    /// # Safety
+    ///
+    /// Same as [push][RingBuffer::push()]: the interrupt must be the only producer.
+    #[inline]
+    pub unsafe fn receive(&self, usart: Usart) {
+        if usart.rx_buffer_not_empty() {
+            // Reading the byte clears the interrupt.
+            self.push(usart.read_data_reg());
+        }
+    }
+
+    /// Returns whether bytes were dropped, and clears the flag.
+    #[inline]
+    pub fn take_overrun(&self) -> bool {
+        self.overrun.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Consumer side of a [RingBuffer], without the size.
+pub(super) trait RxBuffer: Sync {
+    /// Safety: see [RingBuffer::pop].
+    unsafe fn pop(&self) -> Option<u8>;
+    fn take_overrun(&self) -> bool;
+}
+
+impl<const N: usize> RxBuffer for RingBuffer<N> {
+    #[inline]
+    unsafe fn pop(&self) -> Option<u8> {
+        RingBuffer::pop(self)
+    }
+
+    #[inline]
+    fn take_overrun(&self) -> bool {
+        RingBuffer::take_overrun(self)
+    }
+}
@@ -1,13 +1,61 @@
+use crate::{clock, delay, gpio};
 use stm32f1xx_hal::pac::{RCC, USB};
 use stm32_usbd::UsbPeripheral;
 
-pub struct Peripheral {}
+/// Force the host to re-enumerate the device.
+///
+/// Pulls the D+ pin (PA12) low for 10ms, which the host sees as a disconnect, and then releases
+/// it as a floating input. The blue pill has a fixed pull up on D+, so the host would otherwise
+/// not notice a reset of the microcontroller.
+///
+/// Must run before constructing the `UsbBus`.
+pub fn force_reenumerate() {
+    gpio::configure(gpio::PA12, gpio::Mode::OuputPushPull(gpio::Speed::Max50MHz));
+    gpio::write(gpio::PA12, false);
+    delay::millis(10);
+    gpio::configure(gpio::PA12, gpio::Mode::FloatingInput);
+}
+
+/// USB peripheral, using the first `EP_BYTES` bytes of the packet buffer SRAM.
+///
+/// The packet buffer SRAM is 512 bytes, of 16-bit words. The CPU accesses each word at a 32-bit
+/// aligned address, so it spans 1KB starting at `0x4000_6000`. The buffer table and all endpoint
+/// buffers are placed from the start of the SRAM, within `EP_BYTES`.
+///
+/// The same SRAM holds the CAN mailboxes, so USB and CAN can not be used at the same time on the
+/// STM32F103. Reducing `EP_BYTES` only limits the memory taken by USB, it does not allow both.
+///
+/// ```
+/// // Use the lower 256 bytes only.
+/// let usb = usb::Peripheral::<256>::new();
+/// let usb_bus = UsbBus::new(usb);
+/// ```
+pub struct Peripheral<const EP_BYTES: usize = 512> {}
+
+impl<const EP_BYTES: usize> Peripheral<EP_BYTES> {
+    /// Panics if the [USB clock][clock::usb_clock_hz()] is not 48MHz, e.g. with a 25MHz crystal.
+    #[inline]
+    pub fn new() -> Self {
+        assert!(clock::usb_clock_hz() == 48_000_000, "USB requires a 48MHz USB clock");
+        Self {}
+    }
+}
+
+impl<const EP_BYTES: usize> Default for Peripheral<EP_BYTES> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-unsafe impl UsbPeripheral for Peripheral {
+unsafe impl<const EP_BYTES: usize> UsbPeripheral for Peripheral<EP_BYTES> {
     const REGISTERS: *const () = USB::ptr() as *const ();
     const DP_PULL_UP_FEATURE: bool = false;
     const EP_MEMORY: *const () = 0x4000_6000 as _;
-    const EP_MEMORY_SIZE: usize = 512;
+    const EP_MEMORY_SIZE: usize = {
+        assert!(EP_BYTES <= 512 && EP_BYTES % 2 == 0);
+        EP_BYTES
+    };
     const EP_MEMORY_ACCESS_2X16: bool = false;
 
     fn enable() {
@@ -6,17 +6,15 @@ mod telemetry;
 
 extern crate panic_halt;
 
-use bluepill::delay;
-use bluepill::{clock, gpio, gpio::Mode, timer, uart, Led};
-use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use bluepill::{debug_println, delay};
+use bluepill::{clock, device, gpio, gpio::Mode, iwdg, time, timer, uart, Led};
 use cortex_m::peripheral::NVIC;
-use cortex_m_rt::entry;
-use cortex_m_semihosting::hprintln;
+use cortex_m_rt::{entry, exception};
 use motor_driver::Motors;
 use stm32_usbd::UsbBus;
 use stm32f1xx_hal::pac::interrupt;
 use stm32f1xx_hal::pac::Interrupt;
-use telemetry::{LegoMotorPoller, TelemetrySource};
+use telemetry::{LegoMotorPoller, LegoTelemetrySource};
 use usb_device::{
     class_prelude::UsbBusAllocator,
     prelude::{UsbDeviceBuilder, UsbVidPid},
@@ -43,38 +41,39 @@ const LEDS: [gpio::Gpio; 3] = [gpio::PB5, gpio::PA10, gpio::PB15];
 const ENABLE_LEGO: [gpio::Gpio; 3] = [gpio::PA0, gpio::PB3, gpio::PB4];
 
 /// Buffers containing the telemetry feedback from the motors.
-static mut TELEMETRY_SOURCE: [TelemetrySource; 3] = [
-    TelemetrySource::new(),
-    TelemetrySource::new(),
-    TelemetrySource::new(),
+static TELEMETRY_SOURCE: [LegoTelemetrySource; 3] = [
+    LegoTelemetrySource::new(),
+    LegoTelemetrySource::new(),
+    LegoTelemetrySource::new(),
 ];
 
 /// GPIO mode for the LED pins.
 const LED_MODE: gpio::OutputMode = gpio::OutputMode::PushPull(gpio::Speed::Max2MHz);
 
-/// Counter for timing the polling of the motors.
-static POLL_COUNTER: AtomicU8 = AtomicU8::new(0u8);
-
-/// Flags for which motors are enabled (connected).
-static ENABLED: [AtomicBool; 3] = [
-    AtomicBool::new(false),
-    AtomicBool::new(false),
-    AtomicBool::new(false),
-];
-
 /// Settings for blinking the LEDs.
 const LED_TIMER_ARR: usize = 4096; // Timer auto reset register.
-const LED_TIMER_CMP: usize = 2048; // Timer comparator value.
 const LED_TIMER_CMP_BLIMP: usize = 128; // Shorter timer comparator value.
 
+/// Ticks per phase of the status LED blink pattern: 32 phases per blink period.
+const LED_TICKS_PER_PHASE: u32 = LED_TIMER_ARR as u32 / 32;
+
 /// Motor turns off if connection is timed out.
 const MOTOR_CMD_TIMEOUT: usize = 128;
 
+/// Maximum PWM change per PWM period, or None to apply motor commands instantly.
+const PWM_RAMP_RATE: Option<u16> = None;
+
+/// Number of times the LEGO motor initialization is retried.
+const LEGO_INIT_RETRIES: usize = 2;
+
+/// Board resets if the main loop hangs for this long [ms].
+const MAIN_LOOP_TIMEOUT: u32 = 500;
+
 #[entry]
 fn main() -> ! {
-    if cfg!(debug_assertions) {
-        hprintln!("Hello LEGO!").unwrap();
-    }
+    let reset_cause = device::reset_cause();
+    debug_println!("Hello LEGO!");
+    debug_println!("Reset cause = {:?}", reset_cause);
 
     // System setup:
 
@@ -88,13 +87,13 @@ fn main() -> ! {
     // Wait for peripherals to enable.
     delay::millis(1);
 
+    // Millisecond time base.
+    let cp = cortex_m::Peripherals::take().unwrap();
+    time::init(cp.SYST);
+
     // CAN addres:
-    for &id in CAN_ID.iter() {
-        gpio::configure(id, Mode::InputPullUp);
-    }
-    if cfg!(debug_assertions) {
-        hprintln!("CAN ID = NONE").unwrap();
-    }
+    gpio::configure_all(&CAN_ID, Mode::InputPullUp);
+    debug_println!("CAN ID = NONE");
 
     // LEDs:
     let mut led = Led::new(LED, LED_MODE);
@@ -102,13 +101,13 @@ fn main() -> ! {
     let mut leds = LEDS.map(|led| Led::new(led, LED_MODE));
 
     // LEGO motor 3V3 pwr enable pins:
-    for &en in ENABLE_LEGO.iter() {
-        gpio::configure(en, gpio::Mode::OutputOpenDrain(gpio::Speed::Max2MHz));
-        gpio::write(en, true);
-    }
+    gpio::configure_all(&ENABLE_LEGO, gpio::Mode::OutputOpenDrain(gpio::Speed::Max2MHz));
+    gpio::write_all(&ENABLE_LEGO, true);
 
     // Motor FET driver.
     let mut motors = Motors::new();
+    motors.set_ramp_rate(PWM_RAMP_RATE);
+    motors.set_command_timeout(MOTOR_CMD_TIMEOUT);
     motors.enable(true);
 
     // Turn on interrupt on Timer3 for polling.
@@ -116,12 +115,11 @@ fn main() -> ! {
     timer::TIM3.update_interrupt_enable();
 
     // LEGO motor telemetry:
-    if cfg!(debug_assertions) {
-        hprintln!("Connecting to LEGO motors...").unwrap();
-    }
+    debug_println!("Connecting to LEGO motors...");
     let config_uart = uart::Config {
         baudrate: 115200,
         tx_pin: gpio::OutputMode::PushPull(gpio::Speed::Max10MHz),
+        ..Default::default()
     };
     let mut lego_poller = [None, None, None];
     for i in 0..3 {
@@ -129,35 +127,30 @@ fn main() -> ! {
         // Enable power to lego motor.
         gpio::write(ENABLE_LEGO[i], false);
         // Initialize motor.
-        lego_poller[i] = LegoMotorPoller::new(config_uart.make(UART[i]));
+        lego_poller[i] = LegoMotorPoller::new_with_retries(
+            config_uart.make(UART[i]),
+            LEGO_INIT_RETRIES,
+            ENABLE_LEGO[i],
+        );
         // Update status leds.
         let motor_ok = lego_poller[i].is_some();
-        // turn on interrupt.
-        ENABLED[i].store(motor_ok, Ordering::Relaxed);
         leds[i].write(motor_ok);
         // Control power to lego motor.
         gpio::write(ENABLE_LEGO[i], !motor_ok);
-        if cfg!(debug_assertions) {
-            if motor_ok {
-                hprintln!("    Motor{} online", i).unwrap();
-            } else {
-                hprintln!("    Motor{} offline", i).unwrap();
-            }
+        if motor_ok {
+            debug_println!("    Motor{} online", i);
+        } else {
+            debug_println!("    Motor{} offline", i);
         }
     }
 
-    if cfg!(debug_assertions) {
-        hprintln!("Open USB connection.").unwrap();
-    }
+    debug_println!("Open USB connection.");
 
     // Pull the D+ pin down to send a RESET condition to the USB bus.
-    gpio::configure(gpio::PA12, gpio::Mode::OuputPushPull(gpio::Speed::Max50MHz));
-    gpio::write(gpio::PA12, false);
-    delay::millis(10);
-    gpio::configure(gpio::PA12, gpio::Mode::FloatingInput);
+    bluepill::usb::force_reenumerate();
 
     // USB:
-    let usb = bluepill::usb::Peripheral {};
+    let usb = bluepill::usb::Peripheral::new();
     let usb_bus: UsbBusAllocator<UsbBus<bluepill::usb::Peripheral>> = UsbBus::new(usb);
     let mut usb_serial = SerialPort::new(&usb_bus);
     let mut usb_dev = UsbDeviceBuilder::new(&usb_bus, UsbVidPid(0x16c0, 0x27dd))
@@ -169,26 +162,35 @@ fn main() -> ! {
     let mut usb_rx_buf = [0u8; 64]; // Buffer for receiving pwm commands.
     let mut usb_tx_buf = [0u8; 24]; // Buffer for transmitting motor telemetry.
 
-    let mut watchdog_motor_cmd: usize = MOTOR_CMD_TIMEOUT;
     let mut timer_led: usize = 0;
 
+    // Reset the board if the main loop hangs.
+    let mut watchdog = iwdg::Iwdg::start(MAIN_LOOP_TIMEOUT);
+
     // Entering main loop.
     loop {
+        watchdog.feed();
+
+        // Poll the LEGO motors for telemetry.
+        let now = time::millis();
+        for poller in lego_poller.iter_mut().flatten() {
+            poller.poll_if_due(now);
+        }
+
         // Timer1 runs at 2kHz for the PWM signals.
         if timer::TIM1.read_update_interrupt_flag() {
             timer::TIM1.clear_update_interrupt_flag();
-            watchdog_motor_cmd = watchdog_motor_cmd.saturating_add(1);
+            motors.tick();
+            motors.update();
+            led.tick();
             timer_led = (timer_led + 1) % LED_TIMER_ARR;
         }
 
-        // Must receive motor commands every 100ms.
-        let motor_timed_out = watchdog_motor_cmd >= MOTOR_CMD_TIMEOUT;
-        if motor_timed_out {
-            motors.off_ground();
-        }
+        // Must receive motor commands every 100ms, motors time out independently.
+        let motor_timed_out = (0..3).all(|i| motors.is_timed_out(i));
 
         // Read LEGO telemetry sample: Leds flash when receiving samples.
-        for (i, s) in unsafe { TELEMETRY_SOURCE.iter().enumerate() } {
+        for (i, s) in TELEMETRY_SOURCE.iter().enumerate() {
             if let Ok(Some(s)) = s.try_read_sample() {
                 usb_tx_buf[i * 8] = 1;
                 s.write_be_bytes(&mut usb_tx_buf[i * 8 + 1..]);
@@ -196,14 +198,9 @@ fn main() -> ! {
             }
         }
 
-        // Status LED.
-        if !motor_timed_out {
-            // Solid ON if connected.
-            led.on();
-        } else {
-            // Blink if no motor commands received.
-            led.write(timer_led <= LED_TIMER_CMP);
-        }
+        // Status LED: Solid ON if connected, blink if no motor commands received.
+        let pattern = if motor_timed_out { Led::SLOW } else { Led::SOLID };
+        led.blink_pattern(pattern, LED_TICKS_PER_PHASE);
 
         // Status LED: Short blimps if connected.
         if timer_led <= LED_TIMER_CMP_BLIMP {
@@ -223,7 +220,6 @@ fn main() -> ! {
         }
 
         motors.set_raw_pwm(&usb_rx_buf[0..6]);
-        watchdog_motor_cmd = 0;
 
         // Transmit LEGO telemetry over USB.
         let mut write_offset = 0;
@@ -254,6 +250,7 @@ fn USART1() {
         // Reading the byte clears the interrupt.
         let byte = UART1.read_data_reg();
         // Push the byte to the Lego Telemetry Buffer.
+        // Safety: this interrupt is the only writer of the buffer.
         let _ = unsafe { TELEMETRY_SOURCE[2].write_byte(byte) };
     }
 }
@@ -263,6 +260,7 @@ fn USART1() {
 fn USART2() {
     if UART2.rx_buffer_not_empty() {
         let byte = UART2.read_data_reg();
+        // Safety: this interrupt is the only writer of the buffer.
         let _ = unsafe { TELEMETRY_SOURCE[0].write_byte(byte) };
     }
 }
@@ -272,20 +270,23 @@ fn USART2() {
 fn USART3() {
     if UART3.rx_buffer_not_empty() {
         let byte = UART3.read_data_reg();
+        // Safety: this interrupt is the only writer of the buffer.
         let _ = unsafe { TELEMETRY_SOURCE[1].write_byte(byte) };
     }
 }
 
-/// TIMER3 interrupt: used to poll the motors at ~10Hz.
+/// TIMER3 interrupt: used to time out partial telemetry frames.
 #[interrupt]
 fn TIM3() {
     timer::TIM3.clear_update_interrupt_flag();
-    if POLL_COUNTER.fetch_add(1, Ordering::Relaxed) == 180 {
-        POLL_COUNTER.store(0, Ordering::Relaxed);
-        for i in 0..3 {
-            if ENABLED[i].load(Ordering::Relaxed) {
-                UART[i].write_data_reg(telemetry::POLL);
-            }
-        }
+    // Same priority as the USART interrupts.
+    for source in TELEMETRY_SOURCE.iter() {
+        source.tick_timeout();
     }
 }
+
+/// SysTick exception: millisecond time base.
+#[exception]
+fn SysTick() {
+    time::tick();
+}
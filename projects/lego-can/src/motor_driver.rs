@@ -5,9 +5,10 @@
 //! Voltage control is achieved using PWM on the high level MOSFETs.
 //! Direction is controlled by correctly combining which MOSFETs to turn on when.
 
-use bluepill::gpio;
+use bluepill::{adc, gpio};
 use bluepill::timer;
 use bluepill::timer::pwm;
+use core::iter;
 
 /// Hardware layout:
 
@@ -44,9 +45,18 @@ const PSC: u16 = 0;
 const PWM_MODE: pwm::Mode = pwm::Mode::Pwm1;
 const PWM_POLARITY: pwm::Polarity = pwm::Polarity::ActiveHigh;
 
+/// Command timeout in ticks, if not [configured][Motors::set_command_timeout()]:
+const DEFAULT_TIMEOUT: usize = 128;
+
 /// GPIO output mode:
 const GPIO_MODE: gpio::OutputMode = gpio::OutputMode::PushPull(gpio::Speed::Max50MHz);
 
+/// Current sense resistor [mOhm]:
+const SENSE_RESISTOR: u32 = 100;
+
+/// ADC reference voltage [mV]:
+const ADC_VREF: u32 = 3300;
+
 /// Motors PWM driver.
 ///
 /// Consists of three H-bridge motor drivers.
@@ -54,9 +64,24 @@ const GPIO_MODE: gpio::OutputMode = gpio::OutputMode::PushPull(gpio::Speed::Max5
 /// PWM frequency is set to 2048Hz, with [i16::MAX] steps.
 ///
 /// Must call [Motors::enable()] to enable.
+///
+/// Each motor is turned off if no command was received for the
+/// [command timeout][Motors::set_command_timeout()].
 pub struct Motors {
     /// Three [FullBridge] drivers, one for each motor.
     motors: [FullBridge; 3],
+    /// Number of ticks since the last command of each motor.
+    stale: [usize; 3],
+    /// Command timeout in ticks.
+    timeout: usize,
+    /// Current sensing, if enabled.
+    current_sense: Option<CurrentSense>,
+}
+
+/// ADC1 with the channels of the three motor sense resistors.
+struct CurrentSense {
+    adc: adc::Adc,
+    channels: [u8; 3],
 }
 
 impl Motors {
@@ -92,7 +117,12 @@ impl Motors {
 
         pwm.iter_mut().for_each(|pwm| pwm.enable());
 
-        let mut out = Self { motors };
+        let mut out = Self {
+            motors,
+            stale: [DEFAULT_TIMEOUT; 3],
+            timeout: DEFAULT_TIMEOUT,
+            current_sense: None,
+        };
         out.off_ground();
 
         out
@@ -114,19 +144,115 @@ impl Motors {
         self.motors.iter_mut().for_each(|m| m.off_ground());
     }
 
+    /// Enable or disable a single motor, leaving the others running.
+    ///
+    /// A disabled motor is turned off by connecting its legs to ground, and ignores PWM commands.
+    #[allow(unused)]
+    pub fn set_motor_enabled(&mut self, index: usize, enable: bool) {
+        self.motors[index].set_enabled(enable);
+    }
+
     /// Set PWM from raw command.
     ///
     /// Buffer must contain atleast six bytes, representing three i16 in big endian format.
     ///
-    /// Each i16 represents the pwm value of the corresponding motor.
+    /// Each i16 represents the pwm value of the corresponding motor. Feeds the command timeout
+    /// of all motors.
     pub fn set_raw_pwm(&mut self, raw_pwm: &[u8]) {
         for i in 0..3 {
             let j = i * 2;
             let pwm = i16::from_be_bytes([raw_pwm[j], raw_pwm[j + 1]]);
+            self.feed(i);
             self.motors[i].pwm(pwm);
         }
     }
 
+    /// Set the number of [ticks][Motors::tick()] after which a motor without commands is turned
+    /// off.
+    pub fn set_command_timeout(&mut self, ticks: usize) {
+        self.timeout = ticks;
+    }
+
+    /// Reset the command timeout of a motor.
+    pub fn feed(&mut self, index: usize) {
+        self.stale[index] = 0;
+    }
+
+    /// Advance the command timeouts, and turn off the motors with stale commands.
+    pub fn tick(&mut self) {
+        for i in 0..3 {
+            self.stale[i] = self.stale[i].saturating_add(1);
+            if self.is_timed_out(i) {
+                self.motors[i].off_ground();
+            }
+        }
+    }
+
+    /// Returns whether the command of the motor is stale.
+    pub fn is_timed_out(&self, index: usize) -> bool {
+        self.stale[index] >= self.timeout
+    }
+
+    /// Limit the PWM change per [update][Motors::update()] of all motors.
+    ///
+    /// With None, commanded PWM values are applied instantly.
+    pub fn set_ramp_rate(&mut self, rate: Option<u16>) {
+        self.motors.iter_mut().for_each(|m| m.set_ramp_rate(rate));
+    }
+
+    /// Slew the PWM of all motors toward their commanded values.
+    ///
+    /// Should be called once every PWM period when ramping is enabled.
+    pub fn update(&mut self) {
+        self.motors.iter_mut().for_each(|m| m.update());
+    }
+
+    /// Enable current sensing, on the analog pins of the sense resistors of the three motors.
+    ///
+    /// The voltage over the low side sense resistor of each motor is sampled by ADC1, see
+    /// [read_current][Motors::read_current()]. Panics if a pin has no ADC channel, or is used by
+    /// the motor driver.
+    ///
+    /// The pins must also be free on the board: PA0 is `ENABLE_LEGO[0]`, PA2 and PA3 are
+    /// USART2.
+    #[allow(unused)]
+    pub fn enable_current_sense(&mut self, pins: [gpio::Gpio; 3]) {
+        let driver_pins = self
+            .motors
+            .iter()
+            .flat_map(|m| m.legs.iter())
+            .flat_map(|leg| iter::once(leg.gnd).chain(iter::once(leg.pwm.gpio())))
+            .chain(iter::once(ENABLE_MOTOR));
+        let driver_channels = driver_pins.filter_map(adc::channel);
+        let channels = pins.map(|pin| adc::channel(pin).expect("Pin has no ADC channel"));
+        for ch in driver_channels {
+            assert!(!channels.contains(&ch), "Current sense pin is used by the motor driver");
+        }
+        for &pin in pins.iter() {
+            gpio::configure(pin, gpio::Mode::AnalogInput);
+        }
+        self.current_sense = Some(CurrentSense {
+            adc: adc::Adc::new(adc::AdcSel::Adc1),
+            channels,
+        });
+    }
+
+    /// Sample the current of the motor [mA].
+    ///
+    /// Blocks for a single conversion. Call from the TIM1 update interrupt, to sample at the
+    /// same point of every PWM period. Requires
+    /// [enable_current_sense][Motors::enable_current_sense()].
+    #[allow(unused)]
+    pub fn read_current(&mut self, motor: usize) -> u16 {
+        let sense = self
+            .current_sense
+            .as_mut()
+            .expect("Current sensing is not enabled");
+        let raw = sense.adc.read_channel(sense.channels[motor]) as u32;
+        let mv = raw * ADC_VREF / adc::MAX as u32;
+        (mv * 1_000 / SENSE_RESISTOR).min(u16::MAX as u32) as u16
+    }
+
     /// Access the H-bridge drivers.
     #[allow(unused)]
     pub fn get_mut(&mut self) -> &mut [FullBridge; 3] {
@@ -137,23 +263,59 @@ impl Motors {
 /// Full H-bridge motor driver.
 pub struct FullBridge {
     legs: [HalfBridge; 2],
+    /// Applied PWM value.
+    pwm: i16,
+    /// Commanded PWM value.
+    target: i16,
+    /// Maximum PWM change per update, or None for no ramping.
+    ramp_rate: Option<u16>,
+    /// Whether PWM commands are accepted.
+    enabled: bool,
 }
 
 impl FullBridge {
     fn new(left: HalfBridge, right: HalfBridge) -> Self {
         Self {
             legs: [left, right],
+            pwm: 0,
+            target: 0,
+            ramp_rate: None,
+            enabled: true,
+        }
+    }
+
+    /// Enable or disable the motor.
+    ///
+    /// Disabling turns off the motor, by connecting both legs to ground.
+    pub fn set_enabled(&mut self, enable: bool) {
+        self.enabled = enable;
+        if !enable {
+            self.off_ground();
         }
     }
 
+    /// Returns whether the motor accepts PWM commands.
+    #[allow(unused)]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
     /// Turns off all FETs.
+    ///
+    /// Stops immediately, also when ramping.
     pub fn off(&mut self) {
+        self.pwm = 0;
+        self.target = 0;
         self.legs.iter_mut().for_each(|leg| leg.off());
     }
 
     /// Turns off, by connecting both legs to ground.
+    ///
+    /// Stops immediately, also when ramping.
     pub fn off_ground(&mut self) {
-        self.legs.iter_mut().for_each(|leg| leg.ground());
+        self.pwm = 0;
+        self.target = 0;
+        self.ground();
     }
 
     /// Set PWM value.
@@ -163,10 +325,43 @@ impl FullBridge {
     /// - Zero PWM: off.
     ///
     /// when off, both legs are connected to ground.
+    ///
+    /// When ramping is enabled, the value is approached by calling [update][FullBridge::update()].
+    /// Ignored when the motor is disabled.
     pub fn pwm(&mut self, pwm: i16) {
+        if !self.enabled {
+            return;
+        }
+        self.target = pwm;
+        if self.ramp_rate.is_none() {
+            self.apply(pwm);
+        }
+    }
+
+    /// Limit the PWM change per [update][FullBridge::update()].
+    ///
+    /// With None, commanded PWM values are applied instantly.
+    pub fn set_ramp_rate(&mut self, rate: Option<u16>) {
+        self.ramp_rate = rate;
+    }
+
+    /// Slew the applied PWM toward the commanded value by at most the ramp rate.
+    pub fn update(&mut self) {
+        if let Some(rate) = self.ramp_rate {
+            let rate = rate as i32;
+            let step = (self.target as i32 - self.pwm as i32).clamp(-rate, rate);
+            if step != 0 {
+                self.apply((self.pwm as i32 + step) as i16);
+            }
+        }
+    }
+
+    /// Drive the legs with the PWM value.
+    fn apply(&mut self, pwm: i16) {
+        self.pwm = pwm;
         let ccr = pwm.abs() as u16;
         if ccr == 0 {
-            self.off_ground();
+            self.ground();
             return;
         }
         let direction = pwm > 0;
@@ -174,6 +369,11 @@ impl FullBridge {
         self.legs[gnd_leg].ground();
         self.legs[pwm_leg].pwm(ccr);
     }
+
+    /// Connect both legs to ground.
+    fn ground(&mut self) {
+        self.legs.iter_mut().for_each(|leg| leg.ground());
+    }
 }
 
 /// One leg of the FullBridge.
@@ -206,6 +406,7 @@ impl HalfBridge {
     fn off(&mut self) {
         gpio::write(self.gnd, false);
         gpio::configure(self.pwm.gpio(), GPIO_MODE.into());
+        self.pwm.output_disable();
     }
 
     /// Low FET on, high FET off.
@@ -217,6 +418,7 @@ impl HalfBridge {
     /// Low FET off, high FET pwm.
     fn pwm(&mut self, pwm: u16) {
         gpio::write(self.gnd, false);
+        self.pwm.set_enabled(true);
         gpio::configure(self.pwm.gpio(), GPIO_MODE.as_af().into());
         self.pwm.write_ccr(pwm);
     }
@@ -3,31 +3,93 @@ mod sample;
 mod telemetry_source;
 
 use initialization::initialization;
-pub use telemetry_source::{TelemetrySource, DataFrame, FRAME_LEN};
-pub use sample::Sample;
+pub use telemetry_source::{TelemetrySource, DataFrame, Protocol, FRAME_LEN, START};
+pub use sample::LegoProtocol;
 
-use bluepill::uart;
+use bluepill::{delay, gpio, uart};
 
 pub const POLL: u8 = 0x04;
 
+/// Default interval between polls [ms]: the motor must be polled every 100ms.
+pub const POLL_INTERVAL_MS: u32 = 100;
+
+/// Time the motor is powered off between initialization attempts [ms].
+const POWER_OFF_MS: u32 = 100;
+
+/// [TelemetrySource] for the Lego motor frames.
+pub type LegoTelemetrySource = TelemetrySource<LegoProtocol, FRAME_LEN>;
+
 pub struct LegoMotorPoller {
     bus: uart::Bus,
+    /// Interval between polls [ms].
+    interval_ms: u32,
+    /// Time of the last poll [ms].
+    last_poll_ms: u32,
 }
 
 impl LegoMotorPoller {
     /// Initialize Lego motor communication.
+    #[allow(unused)]
     pub fn new(mut bus: uart::Bus) -> Option<Self> {
         let ok = initialization(&mut bus);
+        Self::finish(bus, ok)
+    }
+
+    /// Initialize Lego motor communication, retrying up to `retries` times.
+    ///
+    /// Before each retry the motor is power cycled using its (active low) power enable pin.
+    pub fn new_with_retries(
+        mut bus: uart::Bus,
+        retries: usize,
+        power_enable: gpio::Gpio,
+    ) -> Option<Self> {
+        let mut ok = initialization(&mut bus);
+        for _ in 0..retries {
+            if ok {
+                break;
+            }
+            gpio::write(power_enable, true);
+            delay::millis(POWER_OFF_MS);
+            gpio::write(power_enable, false);
+            ok = initialization(&mut bus);
+        }
+        Self::finish(bus, ok)
+    }
+
+    /// Enable the receive interrupt if initialization succeeded.
+    fn finish(mut bus: uart::Bus, ok: bool) -> Option<Self> {
         bus.set_intterupts_mask(!ok);
         bus.rx_interrupt_enable(ok);
-        Some(Self{bus}).filter(|_| ok)
+        Some(Self {
+            bus,
+            interval_ms: POLL_INTERVAL_MS,
+            last_poll_ms: 0,
+        })
+        .filter(|_| ok)
     }
 
     /// Polls the Lego motor.
     ///
     /// Motor should be polled every 100ms.
-    #[allow(unused)]
     pub fn poll(&mut self) {
         self.bus.wait_write_byte(POLL);
     }
+
+    /// Set the interval used by [poll_if_due][LegoMotorPoller::poll_if_due()].
+    #[allow(unused)]
+    pub fn set_poll_interval(&mut self, interval_ms: u32) {
+        self.interval_ms = interval_ms;
+    }
+
+    /// Polls the Lego motor if the poll interval has passed since the last poll.
+    ///
+    /// Takes the current time in milliseconds, and returns whether the motor was polled.
+    pub fn poll_if_due(&mut self, now_ms: u32) -> bool {
+        if now_ms.wrapping_sub(self.last_poll_ms) < self.interval_ms {
+            return false;
+        }
+        self.last_poll_ms = now_ms;
+        self.poll();
+        true
+    }
 }
@@ -1,4 +1,10 @@
-use super::{FRAME_LEN, DataFrame};
+use super::{DataFrame, Protocol, FRAME_LEN, START};
+
+/// Speed below which the motor is considered standing still [%].
+const STALL_SPEED: i8 = 2;
+
+/// Commanded PWM above which a standing motor is considered stalled: half of full scale.
+const STALL_PWM: i16 = i16::MAX / 2;
 
 /// Lego telemetry sample.
 ///
@@ -25,6 +31,27 @@ impl Sample {
         }
     }
 
+    /// Rotation speed as percentage of the maximum speed.
+    #[allow(unused)]
+    pub fn speed_percent(&self) -> f32 {
+        self.speed as f32
+    }
+
+    /// Accumulated angle [deg].
+    #[allow(unused)]
+    pub fn angle_degrees(&self) -> i32 {
+        self.angle
+    }
+
+    /// Returns whether the motor appears stalled: (nearly) standing still, while the commanded
+    /// PWM is high.
+    ///
+    /// The PWM value is in the range of [Motors::set_raw_pwm][crate::motor_driver::Motors::set_raw_pwm()].
+    #[allow(unused)]
+    pub fn is_stalled(&self, pwm: i16) -> bool {
+        self.speed.saturating_abs() <= STALL_SPEED && pwm.saturating_abs() >= STALL_PWM
+    }
+
     /// Construct self from raw bytes.
     ///
     /// Buffer must be atleast 7 bytes long.
@@ -38,6 +65,16 @@ impl Sample {
         }
     }
 
+    /// Construct a [DataFrame], as transmitted by the Lego motor.
+    #[allow(unused)]
+    pub fn to_dataframe(self) -> DataFrame {
+        let mut frame = [0u8; FRAME_LEN];
+        frame[0] = START;
+        self.write_be_bytes(&mut frame[1..]);
+        frame[FRAME_LEN - 1] = checksum(&frame[..FRAME_LEN - 1]);
+        frame
+    }
+
     /// Write self to buffer as raw bytes.
     ///
     /// Buffer must be atleast 7 bytes long.
@@ -53,13 +90,27 @@ impl Sample {
     }
 }
 
+/// Lego motor telemetry protocol.
+pub struct LegoProtocol;
+
+impl Protocol<FRAME_LEN> for LegoProtocol {
+    const START: u8 = START;
+
+    type Sample = Sample;
+
+    fn parse(frame: &DataFrame) -> Result<Sample, ()> {
+        Sample::from_dataframe(frame)
+    }
+}
+
 /// Checksum as designed by Lego.
 ///
 /// Checksum8 = NOT(XOR( of previously transmitted bytes)).
+pub fn checksum(bytes: &[u8]) -> u8 {
+    !bytes.iter().fold(0, |xor, &byte| xor ^ byte)
+}
+
+/// Returns whether the last byte of the frame holds the [checksum] of the others.
 fn checksum_checker(buffer: &DataFrame) -> bool {
-    let mut xor = buffer[0];
-    for i in 1..FRAME_LEN - 1 {
-        xor = xor ^ buffer[i];
-    }
-    buffer[FRAME_LEN - 1] == !xor
+    buffer[FRAME_LEN - 1] == checksum(&buffer[..FRAME_LEN - 1])
 }
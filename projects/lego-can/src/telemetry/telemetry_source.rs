@@ -1,5 +1,6 @@
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
 use core::sync::atomic::{AtomicU32, Ordering};
-use super::Sample;
 
 // Length of DataFrame is 10 bytes.
 pub const FRAME_LEN: usize = 10;
@@ -7,6 +8,10 @@ pub const FRAME_LEN: usize = 10;
 // Each frame starts with this byte.
 pub const START: u8 = 0xD8;
 
+/// Number of [ticks][TelemetrySource::tick_timeout()] without a new byte, after which a partially
+/// written frame is dropped.
+pub const FRAME_TIMEOUT: u32 = 10;
+
 /// Dataframe received from LEGO motor.
 ///
 /// - Byte[0]:    Start byte = 0xD8
@@ -19,6 +24,22 @@ pub const START: u8 = 0xD8;
 /// Bytes are in little endian order.
 pub type DataFrame = [u8; FRAME_LEN];
 
+/// Frame protocol of a UART sensor, with frames of `N` bytes.
+///
+/// Used by [TelemetrySource] to detect the start of a frame, and to parse completed frames.
+pub trait Protocol<const N: usize> {
+    /// Each frame starts with this byte.
+    const START: u8;
+
+    /// Sample decoded from a frame.
+    type Sample;
+
+    /// Parse a completed frame.
+    ///
+    /// Returns [Err] if the frame is invalid, e.g. if the checksum fails.
+    fn parse(frame: &[u8; N]) -> Result<Self::Sample, ()>;
+}
+
 /// Status definitions for async friendly reading and writing to the buffer.
 const IDLE: u32 = 0; // Buffer is ready for writing.
 const WRITING: u32 = 1; // Buffer is busy being written.
@@ -27,6 +48,8 @@ const READING: u32 = 3; // Buffer is busy being read.
 
 /// Async friendly buffer for telemetry feedback.
 ///
+/// Frames of `N` bytes are parsed according to the [Protocol] `P`.
+///
 /// Intended use:
 ///
 /// Define a global TelemetrySource variable.
@@ -35,7 +58,10 @@ const READING: u32 = 3; // Buffer is busy being read.
 ///
 /// Make sure that `try_read_sample` is polled faster than the max telemetry feedback rate = 250Hz,
 /// or risc missing samples.
-pub struct TelemetrySource {
+///
+/// The buffer can live in a plain `static`. Writing is `unsafe`, as there must be a single
+/// writer, e.g. one interrupt.
+pub struct TelemetrySource<P, const N: usize> {
     /// Used to sync reading and writing of the buffer.
     ///
     /// 0 = IDLE
@@ -44,20 +70,33 @@ pub struct TelemetrySource {
     /// 3 = READING
     status: AtomicU32,
     /// Buffer for holding the data frame.
-    data: DataFrame,
+    ///
+    /// Only accessed by the writer while WRITING, and by the reader while READING.
+    data: UnsafeCell<[u8; N]>,
     /// Index of byte currently being written.
-    write_index: u32,
+    write_index: AtomicU32,
+    /// Number of timeout ticks since the last written byte.
+    stale: AtomicU32,
+    protocol: PhantomData<P>,
 }
 
-impl TelemetrySource {
+impl<P, const N: usize> TelemetrySource<P, N> {
     pub const fn new() -> Self {
         Self {
             status: AtomicU32::new(0),
-            data: [0u8; FRAME_LEN],
-            write_index: 0,
+            data: UnsafeCell::new([0u8; N]),
+            write_index: AtomicU32::new(0),
+            stale: AtomicU32::new(0),
+            protocol: PhantomData,
         }
     }
+}
 
+// Safety: access to the data buffer is synchronized by the status, which hands it either to the
+// single writer or to a reader. A single writer is required by [TelemetrySource::write_byte].
+unsafe impl<P, const N: usize> Sync for TelemetrySource<P, N> {}
+
+impl<P: Protocol<N>, const N: usize> TelemetrySource<P, N> {
     /// Push byte to the buffer.
     ///
     /// This method will lock the buffer, preventing reading the buffer.
@@ -65,8 +104,13 @@ impl TelemetrySource {
     ///
     /// This method returns an error if:
     /// - the previous sample was not read when starting a new sample,
-    /// - the first byte does not equal `START`,
-    pub fn write_byte(&mut self, byte: u8) -> Result<(), ()> {
+    /// - the first byte does not equal [Protocol::START],
+    ///
+    /// # Safety
+    ///
+    /// Must only be called from a single context, e.g. one interrupt routine. Concurrent writers
+    /// race on the buffer.
+    pub unsafe fn write_byte(&self, byte: u8) -> Result<(), ()> {
         if let Err(status) =
             self.status
                 .compare_exchange(IDLE, WRITING, Ordering::Acquire, Ordering::Relaxed)
@@ -75,40 +119,65 @@ impl TelemetrySource {
                 // Status must have been either DONEWRITING or READING.
                 // In this case the reader is too slow in readng the data.
                 // Reset the counter such that the START byte is picked up.
-                self.write_index = 0;
+                self.write_index.store(0, Ordering::Relaxed);
                 return Err(());
             }
         }
 
+        self.stale.store(0, Ordering::Relaxed);
+
         // Store the byte in the buffer.
-        let i = self.write_index as usize;
-        self.data[i] = byte;
+        let i = self.write_index.load(Ordering::Relaxed) as usize;
+        // Safety: the buffer is owned by the single writer while WRITING.
+        (*self.data.get())[i] = byte;
 
         // Check start byte.
-        let start_failed = (i == 0) && (byte != START);
+        let start_failed = (i == 0) && (byte != P::START);
         if start_failed {
-            self.write_index = 0;
+            self.write_index.store(0, Ordering::Relaxed);
             self.status.store(IDLE, Ordering::Relaxed);
             return Err(());
         }
 
         // Update the byte index.
-        self.write_index = ((i + 1) % FRAME_LEN) as u32;
+        let next = (i + 1) % N;
+        self.write_index.store(next as u32, Ordering::Relaxed);
 
-        if self.write_index == 0 {
-            self.status.store(DONEWRITING, Ordering::Relaxed);
+        if next == 0 {
+            // Hand the buffer to the reader.
+            self.status.store(DONEWRITING, Ordering::Release);
         }
 
         Ok(())
     }
 
+    /// Drop a partially written frame, if no byte was written for [FRAME_TIMEOUT] ticks.
+    ///
+    /// Recovers from a sensor being unplugged mid-frame: the next byte is treated as a potential
+    /// [Protocol::START] byte again.
+    ///
+    /// Call at a fixed rate, from an interrupt which does not preempt, and is not preempted by, the
+    /// writer. E.g. a timer interrupt with the same priority as the UART interrupt.
+    pub fn tick_timeout(&self) {
+        if self.status.load(Ordering::Relaxed) != WRITING {
+            return;
+        }
+        if self.stale.fetch_add(1, Ordering::Relaxed) + 1 >= FRAME_TIMEOUT {
+            self.stale.store(0, Ordering::Relaxed);
+            self.write_index.store(0, Ordering::Relaxed);
+            let _ = self
+                .status
+                .compare_exchange(WRITING, IDLE, Ordering::Relaxed, Ordering::Relaxed);
+        }
+    }
+
     /// Read the telemetry sample, if available.
     ///
     /// Returns None if the buffer is locked.
     /// Returns Error if the buffer was already locked for reading.
     ///
     /// This method locks the buffer while reading, and releases the lock when complete.
-    pub fn try_read_sample(&self) -> Result<Option<Sample>, ()> {
+    pub fn try_read_sample(&self) -> Result<Option<P::Sample>, ()> {
         // Try to lock the buffer for reading.
         match self.status.compare_exchange(
             DONEWRITING,
@@ -118,8 +187,9 @@ impl TelemetrySource {
         ) {
             Ok(_) => {
                 // Succes! Read the sample, and reset the status.
-                let sample = Sample::from_dataframe(&self.data)?;
-                self.status.store(IDLE, Ordering::Relaxed);
+                // Safety: the buffer is owned by the reader while READING.
+                let sample = P::parse(unsafe { &*self.data.get() })?;
+                self.status.store(IDLE, Ordering::Release);
                 Ok(Some(sample))
             }
             Err(READING) => {